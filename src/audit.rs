@@ -0,0 +1,125 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use serde::Serialize;
+use sha3::{Digest, Sha3_256};
+
+use crate::{
+  breach::BreachList,
+  common::TecResult,
+  model::Account,
+  repo::{AccountRepo, PwdRepo},
+  strength::estimate,
+  tui::current_millis,
+};
+
+const DAY_MILLIS: u128 = 24 * 60 * 60 * 1000;
+
+// what is wrong with the current password of an account
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Issue {
+  Weak {
+    // from 0 to 4
+    score: u8,
+    reason: Option<&'static str>,
+  },
+  Breached {
+    // times seen in breaches
+    count: u64,
+  },
+  Reused {
+    // ids of the other accounts with the same password
+    with: Vec<u32>,
+  },
+  Old {
+    days: u64,
+  },
+}
+
+impl fmt::Display for Issue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Weak {
+        reason: Some(reason),
+        ..
+      } => write!(f, "weak, {reason}"),
+      Self::Weak { reason: None, .. } => write!(f, "weak"),
+      Self::Breached { count } => write!(f, "seen {count} times in breaches"),
+      Self::Reused { with } => {
+        let ids: Vec<String> = with.iter().map(u32::to_string).collect();
+        write!(f, "reused by {}", ids.join(", "))
+      }
+      Self::Old { days } => write!(f, "unchanged for {days} days"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+  #[serde(flatten)]
+  pub account: Account,
+  pub issues: Vec<Issue>,
+}
+
+// check the current password of every account, `max_age` of `None` skips the age and
+// `breaches` of `None` skips the breached passwords
+pub fn audit(
+  account_repo: &AccountRepo,
+  pwd_repo: &PwdRepo,
+  max_age: Option<Duration>,
+  breaches: Option<&BreachList>,
+) -> TecResult<Vec<Finding>> {
+  let now = current_millis();
+  let mut findings = vec![];
+  // only the digests of the passwords are kept to find the reused ones
+  let mut digests: HashMap<Vec<u8>, Vec<u32>> = HashMap::new();
+
+  for account in account_repo.all()? {
+    let pwds = pwd_repo.query(account.id)?;
+    let Some(pwd) = pwds.first() else {
+      continue;
+    };
+    let mut issues = vec![];
+
+    let strength = estimate(&pwd.password);
+    if strength.is_weak() {
+      issues.push(Issue::Weak {
+        score: strength.score,
+        reason: strength.pattern.map(|p| p.warning()),
+      });
+    }
+
+    if let Some(list) = breaches {
+      if let Some(count) = list.count(&pwd.password)? {
+        issues.push(Issue::Breached { count });
+      }
+    }
+
+    let age = now.saturating_sub(pwd.created as u128);
+    if max_age.is_some_and(|max_age| age > max_age.as_millis()) {
+      issues.push(Issue::Old {
+        days: (age / DAY_MILLIS) as u64,
+      });
+    }
+
+    let digest = Sha3_256::digest(pwd.password.as_bytes()).to_vec();
+    digests.entry(digest).or_default().push(account.id);
+    findings.push(Finding { account, issues });
+  }
+
+  for ids in digests.values().filter(|ids| ids.len() > 1) {
+    for finding in findings.iter_mut() {
+      if ids.contains(&finding.account.id) {
+        let with = ids
+          .iter()
+          .copied()
+          .filter(|id| *id != finding.account.id)
+          .collect();
+        finding.issues.push(Issue::Reused { with });
+      }
+    }
+  }
+
+  findings.retain(|finding| !finding.issues.is_empty());
+  Ok(findings)
+}
@@ -0,0 +1,276 @@
+use std::{
+  cmp::Ordering,
+  fmt::Write as _,
+  fs::{self, File},
+  io::{BufRead, BufReader, Seek, SeekFrom},
+  path::{Path, PathBuf},
+};
+
+use md4::Md4;
+use sha1::{Digest, Sha1};
+use zeroize::Zeroizing;
+
+use crate::{
+  common::{TecError, TecResult},
+  model::Account,
+  repo::PwdRepo,
+};
+
+// hex digits of a hash that name its range file
+const PREFIX_LEN: usize = 5;
+// below this many bytes, the rest of the search reads the lines one by one
+const SCAN_WINDOW: u64 = 4096;
+
+// the hashes of the Have I Been Pwned password lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashKind {
+  Sha1,
+  Ntlm,
+}
+
+impl HashKind {
+  // detected from the length of the hashes in the list
+  fn from_len(len: usize) -> Option<Self> {
+    match len {
+      40 => Some(Self::Sha1),
+      32 => Some(Self::Ntlm),
+      _ => None,
+    }
+  }
+
+  // uppercase hex, as in the lists
+  fn hash(&self, password: &str) -> Zeroizing<String> {
+    let digest = match self {
+      Self::Sha1 => Sha1::digest(password.as_bytes()).to_vec(),
+      Self::Ntlm => {
+        let utf16: Zeroizing<Vec<u8>> =
+          Zeroizing::new(password.encode_utf16().flat_map(u16::to_le_bytes).collect());
+        Md4::digest(&utf16[..]).to_vec()
+      }
+    };
+    let mut hex = Zeroizing::new(String::with_capacity(digest.len() * 2));
+    for b in digest {
+      let _ = write!(hex, "{b:02X}");
+    }
+    hex
+  }
+}
+
+/*
+An offline copy of the Have I Been Pwned passwords, either a single file of every hash
+sorted in order:
+  5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004
+or a directory of range files, named by the first 5 hex digits of the hashes, e.g.
+`5BAA6` or `5BAA6.txt`, each holding the rest of its hashes in order:
+  1E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004
+Either is searched in place, without reading it all.
+*/
+#[derive(Debug, Clone)]
+pub struct BreachList {
+  path: PathBuf,
+  kind: HashKind,
+  ranges: bool,
+}
+
+impl BreachList {
+  pub fn open(path: impl Into<PathBuf>) -> TecResult<Self> {
+    let path = path.into();
+    if !path.exists() {
+      return Err(TecError::InvalidHashList(format!(
+        "{} not found",
+        path.display()
+      )));
+    }
+    let ranges = path.is_dir();
+    let sample = if ranges {
+      range_sample(&path)?
+    } else {
+      path.clone()
+    };
+    let mut line = String::new();
+    BufReader::new(File::open(&sample)?).read_line(&mut line)?;
+    let len = hash_of(&line).len() + if ranges { PREFIX_LEN } else { 0 };
+    let Some(kind) = HashKind::from_len(len) else {
+      return Err(TecError::InvalidHashList(format!(
+        "{} does not hold sha1 or ntlm hashes",
+        sample.display()
+      )));
+    };
+    Ok(Self { path, kind, ranges })
+  }
+
+  // how many times the password has been seen in breaches, `None` if it hasn't
+  pub fn count(&self, password: &str) -> TecResult<Option<u64>> {
+    let hash = self.kind.hash(password);
+    if !self.ranges {
+      return search(&mut BufReader::new(File::open(&self.path)?), &hash);
+    }
+    let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+    let file = [format!("{prefix}.txt"), prefix.to_owned()]
+      .into_iter()
+      .map(|name| self.path.join(name))
+      .find(|path| path.exists());
+    // a range missing from the copy would hide a breached password
+    let Some(file) = file else {
+      return Err(TecError::InvalidHashList(format!(
+        "no range file for {prefix} in {}",
+        self.path.display()
+      )));
+    };
+    search(&mut BufReader::new(File::open(file)?), suffix)
+  }
+}
+
+// ids of the accounts whose current password is on the list
+pub fn breached(
+  accounts: &[Account],
+  pwd_repo: &PwdRepo,
+  list: &BreachList,
+) -> TecResult<Vec<u32>> {
+  let mut ids = vec![];
+  for account in accounts {
+    if let Some(pwd) = pwd_repo.query(account.id)?.first() {
+      if list.count(&pwd.password)?.is_some() {
+        ids.push(account.id);
+      }
+    }
+  }
+  Ok(ids)
+}
+
+// any range file, to tell the hashes of the directory
+fn range_sample(dir: &Path) -> TecResult<PathBuf> {
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    let is_range = path
+      .file_stem()
+      .and_then(|stem| stem.to_str())
+      .is_some_and(|stem| stem.len() == PREFIX_LEN && stem.chars().all(|c| c.is_ascii_hexdigit()));
+    if is_range && path.is_file() {
+      return Ok(path);
+    }
+  }
+  Err(TecError::InvalidHashList(format!(
+    "no range files in {}",
+    dir.display()
+  )))
+}
+
+fn hash_of(line: &str) -> &str {
+  let line = line.trim_end();
+  line.split_once(':').map_or(line, |(hash, _)| hash)
+}
+
+/*
+Binary search over the bytes of the file, keeping every line that starts before `lo`
+below the hash, and the hash, if it is there, on a line that starts before `hi`. The
+line read at `mid` is the first one that starts at or after it.
+*/
+fn search<R: BufRead + Seek>(reader: &mut R, hash: &str) -> TecResult<Option<u64>> {
+  let mut lo = 0;
+  let mut hi = reader.seek(SeekFrom::End(0))?;
+  let mut line = String::new();
+  while hi - lo > SCAN_WINDOW {
+    let mid = lo + (hi - lo) / 2;
+    seek_line(reader, mid)?;
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      hi = mid;
+      continue;
+    }
+    match compare(&line, hash) {
+      Ordering::Less => lo = mid,
+      Ordering::Equal => return count_of(&line),
+      Ordering::Greater => hi = mid,
+    }
+  }
+
+  seek_line(reader, lo)?;
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    match compare(&line, hash) {
+      Ordering::Less => continue,
+      Ordering::Equal => return count_of(&line),
+      Ordering::Greater => return Ok(None),
+    }
+  }
+}
+
+// move to the first line that starts at or after `pos`
+fn seek_line<R: BufRead + Seek>(reader: &mut R, pos: u64) -> TecResult<()> {
+  if pos == 0 {
+    reader.seek(SeekFrom::Start(0))?;
+    return Ok(());
+  }
+  reader.seek(SeekFrom::Start(pos - 1))?;
+  let mut skipped = vec![];
+  reader.read_until(b'\n', &mut skipped)?;
+  Ok(())
+}
+
+fn compare(line: &str, hash: &str) -> Ordering {
+  let found = hash_of(line).bytes().map(|b| b.to_ascii_uppercase());
+  found.cmp(hash.bytes())
+}
+
+fn count_of(line: &str) -> TecResult<Option<u64>> {
+  let count = match line.trim_end().split_once(':') {
+    Some((_, count)) => count.trim().parse()?,
+    None => 1,
+  };
+  Ok(Some(count))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+
+  fn sorted_list(n: u32) -> Cursor<Vec<u8>> {
+    let mut content = String::new();
+    for i in 0..n {
+      content.push_str(&format!("{:040X}:{}\r\n", i * 2, i + 1));
+    }
+    Cursor::new(content.into_bytes())
+  }
+
+  #[test]
+  fn hashes() {
+    assert_eq!(
+      *HashKind::Sha1.hash("password"),
+      "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+    );
+    assert_eq!(
+      *HashKind::Ntlm.hash("password"),
+      "8846F7EAEE8FB117AD06BDD830B7586C"
+    );
+  }
+
+  #[test]
+  fn finds_every_line() {
+    let mut list = sorted_list(2000);
+    for i in [0, 1, 999, 1998, 1999] {
+      let hash = format!("{:040X}", i * 2);
+      assert_eq!(search(&mut list, &hash).unwrap(), Some(i as u64 + 1));
+    }
+  }
+
+  #[test]
+  fn misses_absent_hashes() {
+    let mut list = sorted_list(2000);
+    for i in [1, 1001, 3997, 5000] {
+      let hash = format!("{i:040X}");
+      assert_eq!(search(&mut list, &hash).unwrap(), None);
+    }
+  }
+
+  #[test]
+  fn ignores_case() {
+    let mut list = Cursor::new(b"00ab:3\n00cd:4\n".to_vec());
+    assert_eq!(search(&mut list, "00CD").unwrap(), Some(4));
+  }
+}
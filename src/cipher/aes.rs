@@ -1,10 +1,18 @@
-use aes_gcm_siv::{aead::Aead, Aes256GcmSiv, KeyInit, Nonce};
+use aes_gcm_siv::{
+  aead::{Aead, Payload},
+  Aes256GcmSiv, KeyInit, Nonce,
+};
 use rand::RngCore;
+use zeroize::Zeroizing;
 
-use crate::common::TecResult;
+use crate::common::{SecretBytes, TecError, TecResult};
 
 use super::NONCE_LEN;
 
+// ciphertexts bound to where they are stored, prefixed to tell them apart from the bare
+// nonce || ciphertext written before
+const VERSION_AAD: u8 = 1;
+
 pub struct AesCipher {
   aes: Aes256GcmSiv,
 }
@@ -16,19 +24,40 @@ impl AesCipher {
     })
   }
 
-  pub fn encypt(&self, plaintext: &[u8]) -> TecResult<Vec<u8>> {
+  pub fn encypt_aad(&self, plaintext: &[u8], aad: &str) -> TecResult<Vec<u8>> {
     let mut nonce = [0u8; NONCE_LEN];
     rand::thread_rng().fill_bytes(&mut nonce);
 
-    let ciphertext = self.aes.encrypt(Nonce::from_slice(&nonce), plaintext)?;
-    Ok([&nonce[..], ciphertext.as_slice()].concat().to_vec())
+    let payload = Payload {
+      msg: plaintext,
+      aad: aad.as_bytes(),
+    };
+    let ciphertext = self.aes.encrypt(Nonce::from_slice(&nonce), payload)?;
+    Ok([&[VERSION_AAD], &nonce[..], ciphertext.as_slice()].concat())
+  }
+
+  pub fn decypt_aad(&self, enc: &[u8], aad: &str) -> TecResult<SecretBytes> {
+    if enc.len() < 1 + NONCE_LEN || enc[0] != VERSION_AAD {
+      return Err(TecError::InvalidCiphertext(aad.to_string()));
+    }
+    let nonce = &enc[1..(1 + NONCE_LEN)];
+    let payload = Payload {
+      msg: &enc[(1 + NONCE_LEN)..],
+      aad: aad.as_bytes(),
+    };
+
+    let plaintext = self
+      .aes
+      .decrypt(Nonce::from_slice(nonce), payload)
+      .map_err(|_| TecError::InvalidCiphertext(aad.to_string()))?;
+    Ok(Zeroizing::new(plaintext))
   }
 
-  pub fn decypt(&self, enc: &[u8]) -> TecResult<Vec<u8>> {
+  pub fn decypt(&self, enc: &[u8]) -> TecResult<SecretBytes> {
     let nonce = &enc[..NONCE_LEN];
     let ciphertext = &enc[NONCE_LEN..];
 
     let plaintext = self.aes.decrypt(Nonce::from_slice(nonce), ciphertext)?;
-    Ok(plaintext)
+    Ok(Zeroizing::new(plaintext))
   }
 }
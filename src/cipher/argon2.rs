@@ -1,8 +1,14 @@
+use std::time::{Duration, Instant};
+
 use aes_gcm_siv::{aead::Aead, Aes256GcmSiv, KeyInit, Nonce};
-use argon2::Argon2;
+use argon2::{Algorithm, Argon2, Params, Version};
 use rand::RngCore;
+use zeroize::Zeroizing;
 
-use crate::{common::TecResult, hex};
+use crate::{
+  common::{SecretBytes, TecError, TecResult},
+  hex,
+};
 
 use super::NONCE_LEN;
 
@@ -10,43 +16,156 @@ use super::NONCE_LEN;
 const SALT_LEN: usize = 32;
 #[allow(dead_code)]
 const ENC_KEY_LEN: usize = 32;
+// 1 GiB in KiB
+const MAX_M_COST: u32 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+  pub algorithm: Algorithm,
+  pub m_cost: u32,
+  pub t_cost: u32,
+  pub p_cost: u32,
+  pub salt_len: usize,
+}
+
+impl Default for Argon2Params {
+  // the same parameters as `Argon2::default()`, which every key sealed before the
+  // parameters were stored has been derived with
+  fn default() -> Self {
+    Self {
+      algorithm: Algorithm::default(),
+      m_cost: Params::DEFAULT_M_COST,
+      t_cost: Params::DEFAULT_T_COST,
+      p_cost: Params::DEFAULT_P_COST,
+      salt_len: SALT_LEN,
+    }
+  }
+}
+
+impl Argon2Params {
+  // the secret is argon2's optional key input, empty unless a keyfile is used
+  fn argon2<'k>(&self, secret: &'k [u8]) -> TecResult<Argon2<'k>> {
+    let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(ENC_KEY_LEN))?;
+    if secret.is_empty() {
+      return Ok(Argon2::new(self.algorithm, Version::V0x13, params));
+    }
+    Ok(Argon2::new_with_secret(
+      secret,
+      self.algorithm,
+      Version::V0x13,
+      params,
+    )?)
+  }
+
+  // time a single key derivation with these parameters on this machine
+  pub fn measure(&self) -> TecResult<Duration> {
+    let argon2 = self.argon2(&[])?;
+    let mut salt = vec![0u8; self.salt_len];
+    let mut out = [0u8; ENC_KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let start = Instant::now();
+    argon2.hash_password_into(b"tecpass-calibration", &salt, &mut out)?;
+    Ok(start.elapsed())
+  }
+
+  // find parameters that take about `target` to derive a key, never weaker than the
+  // defaults. memory is raised first, as it is what makes argon2 expensive to attack
+  // on GPUs, then the iterations fill up the remaining time.
+  pub fn calibrate(target: Duration) -> TecResult<Self> {
+    let mut params = Self {
+      t_cost: 1,
+      ..Self::default()
+    };
+
+    let mut elapsed = params.measure()?;
+    while elapsed * 3 < target && params.m_cost < MAX_M_COST {
+      params.m_cost = (params.m_cost * 2).min(MAX_M_COST);
+      elapsed = params.measure()?;
+    }
+
+    let t_cost = (target.as_secs_f64() / elapsed.as_secs_f64()).round() as u32;
+    params.t_cost = t_cost.max(1);
+    if params.m_cost == Params::DEFAULT_M_COST {
+      params.t_cost = params.t_cost.max(Params::DEFAULT_T_COST);
+    }
+    Ok(params)
+  }
+}
+
+impl std::fmt::Display for Argon2Params {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} m={} KiB, t={}, p={}, salt={} bytes",
+      self.algorithm, self.m_cost, self.t_cost, self.p_cost, self.salt_len
+    )
+  }
+}
 
 pub struct Argon2Cipher<'a> {
   pwd: &'a [u8],
+  secret: &'a [u8],
+  params: Argon2Params,
 }
 
 impl<'a> Argon2Cipher<'a> {
   pub fn new(pwd: &'a [u8]) -> Self {
-    Self { pwd }
+    Self {
+      pwd,
+      secret: &[],
+      params: Argon2Params::default(),
+    }
+  }
+
+  pub fn with_params(mut self, params: Argon2Params) -> Self {
+    self.params = params;
+    self
+  }
+
+  // mix a secret, e.g. the digest of a keyfile, into the derived key
+  pub fn with_secret(mut self, secret: &'a [u8]) -> Self {
+    self.secret = secret;
+    self
   }
 
   pub fn encrypt(&self, plaintext: &[u8]) -> TecResult<String> {
-    let mut salt = [0u8; SALT_LEN];
+    let mut salt = vec![0u8; self.params.salt_len];
     let mut nonce = [0u8; NONCE_LEN];
-    let mut enc_key = [0u8; ENC_KEY_LEN];
+    let mut enc_key = Zeroizing::new([0u8; ENC_KEY_LEN]);
 
     rand::thread_rng().fill_bytes(&mut salt);
     rand::thread_rng().fill_bytes(&mut nonce);
-    Argon2::default().hash_password_into(self.pwd, &salt, &mut enc_key)?;
+    self
+      .params
+      .argon2(self.secret)?
+      .hash_password_into(self.pwd, &salt, &mut *enc_key)?;
 
-    let cipher = Aes256GcmSiv::new_from_slice(&enc_key)?;
+    let cipher = Aes256GcmSiv::new_from_slice(&*enc_key)?;
     let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), plaintext)?;
 
     let enc = [&salt[..], &nonce[..], ciphertext.as_slice()].concat();
-    Ok(hex::encode(&enc)?)
+    hex::encode(&enc)
   }
 
-  pub fn decrypt(&self, enc_str: &str) -> TecResult<Vec<u8>> {
+  pub fn decrypt(&self, enc_str: &str) -> TecResult<SecretBytes> {
     let enc = hex::decode(enc_str)?;
+    let salt_len = self.params.salt_len;
+    if enc.len() < salt_len + NONCE_LEN {
+      return Err(TecError::InvalidKeyFile("sealed key too short".into()));
+    }
 
-    let salt = &enc[..SALT_LEN];
-    let nonce = &enc[SALT_LEN..(SALT_LEN + NONCE_LEN)];
-    let ciphertext = &enc[(SALT_LEN + NONCE_LEN)..];
+    let salt = &enc[..salt_len];
+    let nonce = &enc[salt_len..(salt_len + NONCE_LEN)];
+    let ciphertext = &enc[(salt_len + NONCE_LEN)..];
 
-    let mut enc_key = [0u8; ENC_KEY_LEN];
-    Argon2::default().hash_password_into(self.pwd, salt, &mut enc_key)?;
-    let cipher = Aes256GcmSiv::new_from_slice(&enc_key)?;
+    let mut enc_key = Zeroizing::new([0u8; ENC_KEY_LEN]);
+    self
+      .params
+      .argon2(self.secret)?
+      .hash_password_into(self.pwd, salt, &mut *enc_key)?;
+    let cipher = Aes256GcmSiv::new_from_slice(&*enc_key)?;
     let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)?;
-    Ok(plaintext)
+    Ok(Zeroizing::new(plaintext))
   }
 }
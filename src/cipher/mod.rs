@@ -2,7 +2,7 @@ mod aes;
 mod argon2;
 
 pub use self::aes::AesCipher;
-pub use self::argon2::Argon2Cipher;
+pub use self::argon2::{Argon2Cipher, Argon2Params};
 
 #[allow(dead_code)]
 const NONCE_LEN: usize = 12;
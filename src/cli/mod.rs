@@ -0,0 +1,439 @@
+mod unlock;
+
+use std::{
+  io::{self, IsTerminal},
+  path::Path,
+  str::FromStr,
+};
+
+use argh::FromArgs;
+use serde::Serialize;
+
+pub use unlock::{read_password, unlock, PasswordSource};
+
+use crate::{
+  audit::audit,
+  common::{SecretString, TecError, TecResult},
+  generator::{generate, passphrase, Policy},
+  model::{Account, Pwd},
+  repo::{open_repos, AccountRepo, PwdRepo},
+  tui::{current_millis, millis2string, AccountOptions, AuthOptions, LengthLimits},
+};
+
+// exit codes, any other error exits with 1
+const EXIT_UNLOCK_FAILED: i32 = 2;
+const EXIT_NOT_FOUND: i32 = 3;
+const EXIT_AMBIGUOUS: i32 = 4;
+const EXIT_ISSUES_FOUND: i32 = 5;
+const EXIT_TAMPERED: i32 = 6;
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+  List(List),
+  Show(Show),
+  GetPassword(GetPassword),
+  Add(Add),
+  Edit(Edit),
+  Rm(Rm),
+  Generate(Generate),
+  Audit(Audit),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Text,
+  Json,
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(Self::Text),
+      "json" => Ok(Self::Json),
+      _ => Err(format!("unknown format {s}, expected text or json")),
+    }
+  }
+}
+
+/// print the accounts, one per line: id, url and username separated by tabs
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "list")]
+pub struct List {
+  /// only the accounts whose url or username contains this
+  #[argh(positional)]
+  query: Option<String>,
+  /// output format: text or json
+  #[argh(option, default = "Format::Text")]
+  format: Format,
+}
+
+/// print an account, without its passwords unless --history is given
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "show")]
+pub struct Show {
+  /// id of the account
+  #[argh(positional)]
+  id: u32,
+  /// also print every password of the account, the newest first
+  #[argh(switch)]
+  history: bool,
+  /// output format: text or json
+  #[argh(option, default = "Format::Text")]
+  format: Format,
+}
+
+/// print a generated password, or a passphrase with --words
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "generate")]
+pub struct Generate {
+  /// number of characters, 20 by default
+  #[argh(option, default = "Policy::default().length")]
+  length: usize,
+  /// no lowercase letters
+  #[argh(switch)]
+  no_lower: bool,
+  /// no uppercase letters
+  #[argh(switch)]
+  no_upper: bool,
+  /// no digits
+  #[argh(switch)]
+  no_digits: bool,
+  /// no symbols
+  #[argh(switch)]
+  no_symbols: bool,
+  /// leave out characters that are easily mistaken for one another, e.g. 0 and O
+  #[argh(switch)]
+  exclude_ambiguous: bool,
+  /// don't require at least one character of every class
+  #[argh(switch)]
+  no_require_each: bool,
+  /// generate a passphrase of this many words instead
+  #[argh(option)]
+  words: Option<usize>,
+  /// separator between the words of a passphrase, - by default
+  #[argh(option, default = "String::from(\"-\")")]
+  separator: String,
+}
+
+impl Generate {
+  fn run(&self) -> TecResult<()> {
+    let pwd = match self.words {
+      Some(words) => passphrase(words, &self.separator)?,
+      None => generate(&Policy {
+        length: self.length,
+        lower: !self.no_lower,
+        upper: !self.no_upper,
+        digits: !self.no_digits,
+        symbols: !self.no_symbols,
+        exclude_ambiguous: self.exclude_ambiguous,
+        require_each: !self.no_require_each,
+      })?,
+    };
+    println!("{}", pwd.as_str());
+    Ok(())
+  }
+}
+
+// an account as printed by `show --format json`, see the README for the schema
+#[derive(Serialize)]
+struct ShowJson<'a> {
+  #[serde(flatten)]
+  account: &'a Account,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  passwords: Option<&'a [Pwd]>,
+}
+
+/// print the current password of an account
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "get-password")]
+pub struct GetPassword {
+  /// id of the account, or a part of its url or username matching a single account
+  #[argh(positional)]
+  account: String,
+}
+
+/// add an account, its password is read from stdin
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "add")]
+pub struct Add {
+  /// url of the account
+  #[argh(option)]
+  url: String,
+  /// username of the account
+  #[argh(option)]
+  username: String,
+}
+
+/// change the url, username or password of an account
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "edit")]
+pub struct Edit {
+  /// id of the account
+  #[argh(positional)]
+  id: u32,
+  /// new url
+  #[argh(option)]
+  url: Option<String>,
+  /// new username
+  #[argh(option)]
+  username: Option<String>,
+  /// read a new password from stdin
+  #[argh(switch)]
+  password: bool,
+}
+
+/// report the accounts whose current password is weak, on --breach-list, reused or older
+/// than --max-age
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "audit")]
+pub struct Audit {
+  /// output format: text or json
+  #[argh(option, default = "Format::Text")]
+  format: Format,
+}
+
+/// delete an account and its password history
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "rm")]
+pub struct Rm {
+  /// id of the account
+  #[argh(positional)]
+  id: u32,
+}
+
+pub fn run(
+  cmd: Command,
+  config_path: &Path,
+  opts: &AuthOptions,
+  source: &PasswordSource,
+  account_opts: AccountOptions,
+) -> TecResult<()> {
+  let password_length = account_opts.password_length;
+  // only unlocked by the commands that need the vault
+  let open = || -> TecResult<(AccountRepo, PwdRepo)> {
+    let key = unlock(config_path, opts, source)?;
+    let (account_repo, pwd_repo, manifest_repo) = open_repos(config_path.join("tecpass.db"), &key)?;
+    // verified as at login, but with nobody to see a warning
+    manifest_repo.check()?;
+    Ok((account_repo, pwd_repo))
+  };
+
+  match cmd {
+    Command::Generate(generate) => generate.run()?,
+    Command::List(list) => {
+      let (account_repo, _) = open()?;
+      let mut accounts = account_repo.all()?;
+      accounts.retain(|a| list.query.as_deref().is_none_or(|q| matches(a, q)));
+      match list.format {
+        Format::Text => {
+          for a in accounts.iter() {
+            println!("{}\t{}\t{}", a.id, a.url, a.username);
+          }
+        }
+        Format::Json => println!("{}", serde_json::to_string(&accounts)?),
+      }
+    }
+    Command::Show(show) => {
+      let (account_repo, pwd_repo) = open()?;
+      let accounts = account_repo.all()?;
+      let a = find_by_id(&accounts, show.id)?;
+      let pwds = pwd_repo.query(a.id)?;
+      match show.format {
+        Format::Text => {
+          println!("id:        {}", a.id);
+          println!("url:       {}", a.url);
+          println!("username:  {}", a.username);
+          println!("created:   {}", millis2string(a.created as u64));
+          println!("changed:   {}", millis2string(a.changed as u64));
+          println!("passwords: {}", pwds.len());
+          if show.history {
+            for pwd in pwds.iter() {
+              println!(
+                "  {}  {}",
+                millis2string(pwd.created as u64),
+                pwd.password.as_str()
+              );
+            }
+          }
+        }
+        Format::Json => {
+          let json = ShowJson {
+            account: a,
+            passwords: show.history.then_some(&pwds[..]),
+          };
+          println!("{}", serde_json::to_string(&json)?);
+        }
+      }
+    }
+    Command::GetPassword(get) => {
+      let (account_repo, pwd_repo) = open()?;
+      let accounts = account_repo.all()?;
+      let a = find(&accounts, &get.account)?;
+      let pwds = pwd_repo.query(a.id)?;
+      let Some(pwd) = pwds.first() else {
+        return Err(TecError::AccountNotFound(get.account));
+      };
+      println!("{}", pwd.password.as_str());
+    }
+    Command::Add(add) => {
+      let (account_repo, pwd_repo) = open()?;
+      let current = current_millis() as usize;
+      let acc = Account {
+        id: 0,
+        url: add.url,
+        username: add.username,
+        created: current,
+        changed: current,
+      };
+      validate(&acc)?;
+      let password = new_password(password_length)?;
+      let aid = account_repo.add(&acc)?;
+      add_password(&pwd_repo, aid, password, current)?;
+      println!("{aid}");
+    }
+    Command::Edit(edit) => {
+      let (account_repo, pwd_repo) = open()?;
+      let accounts = account_repo.all()?;
+      let mut acc = find_by_id(&accounts, edit.id)?.clone();
+      let current = current_millis() as usize;
+      if let Some(url) = edit.url {
+        acc.url = url;
+      }
+      if let Some(username) = edit.username {
+        acc.username = username;
+      }
+      acc.changed = current;
+      validate(&acc)?;
+      let password = if edit.password {
+        Some(new_password(password_length)?)
+      } else {
+        None
+      };
+      account_repo.update(&acc)?;
+      if let Some(password) = password {
+        add_password(&pwd_repo, acc.id, password, current)?;
+      }
+    }
+    Command::Audit(report) => {
+      let (account_repo, pwd_repo) = open()?;
+      let findings = audit(
+        &account_repo,
+        &pwd_repo,
+        account_opts.max_age,
+        account_opts.breaches.as_ref(),
+      )?;
+      match report.format {
+        Format::Text => {
+          for f in findings.iter() {
+            let issues: Vec<String> = f.issues.iter().map(|i| i.to_string()).collect();
+            println!(
+              "{}\t{}\t{}\t{}",
+              f.account.id,
+              f.account.url,
+              f.account.username,
+              issues.join("; ")
+            );
+          }
+        }
+        Format::Json => println!("{}", serde_json::to_string(&findings)?),
+      }
+      if !findings.is_empty() {
+        return Err(TecError::IssuesFound(findings.len()));
+      }
+    }
+    Command::Rm(rm) => {
+      let (account_repo, pwd_repo) = open()?;
+      let accounts = account_repo.all()?;
+      let a = find_by_id(&accounts, rm.id)?;
+      account_repo.delete(a.id)?;
+      pwd_repo.delete(a.id)?;
+    }
+  }
+  Ok(())
+}
+
+pub fn exit_code(err: &TecError) -> i32 {
+  match err {
+    TecError::WrongPassword
+    | TecError::Throttled(_)
+    | TecError::LockedOut
+    | TecError::KeyfileRequired
+    | TecError::KeyfileNotFound(_)
+    | TecError::WrongKeyfile => EXIT_UNLOCK_FAILED,
+    TecError::AccountNotFound(_) => EXIT_NOT_FOUND,
+    TecError::AmbiguousAccount(_) => EXIT_AMBIGUOUS,
+    TecError::IssuesFound(_) => EXIT_ISSUES_FOUND,
+    TecError::Tampered => EXIT_TAMPERED,
+    _ => 1,
+  }
+}
+
+fn matches(a: &Account, query: &str) -> bool {
+  let query = query.to_lowercase();
+  a.url.to_lowercase().contains(&query) || a.username.to_lowercase().contains(&query)
+}
+
+fn find_by_id(accounts: &[Account], id: u32) -> TecResult<&Account> {
+  accounts
+    .iter()
+    .find(|a| a.id == id)
+    .ok_or_else(|| TecError::AccountNotFound(id.to_string()))
+}
+
+// an id wins over an url or username that happens to contain the number
+fn find<'a>(accounts: &'a [Account], query: &str) -> TecResult<&'a Account> {
+  if let Ok(id) = query.parse() {
+    if let Ok(a) = find_by_id(accounts, id) {
+      return Ok(a);
+    }
+  }
+  let found: Vec<&Account> = accounts.iter().filter(|a| matches(a, query)).collect();
+  match found[..] {
+    [] => Err(TecError::AccountNotFound(query.to_owned())),
+    [a] => Ok(a),
+    _ => Err(TecError::AmbiguousAccount(query.to_owned())),
+  }
+}
+
+fn validate(a: &Account) -> TecResult<()> {
+  if a.url.is_empty() || a.username.is_empty() {
+    return Err(TecError::InvalidInput(
+      "url and username can't be empty".into(),
+    ));
+  }
+  Ok(())
+}
+
+// the same bounds as the password field of the form
+fn new_password(limits: LengthLimits) -> TecResult<SecretString> {
+  let password = read_password("account password: ")?;
+  if !limits.contains(password.chars().count()) {
+    return Err(TecError::InvalidInput(format!(
+      "the password needs {limits} characters"
+    )));
+  }
+  // a typo can't be seen without the echo
+  if io::stdin().is_terminal() && *read_password("confirm: ")? != *password {
+    return Err(TecError::InvalidInput("the passwords don't match".into()));
+  }
+  Ok(password)
+}
+
+fn add_password(
+  pwd_repo: &PwdRepo,
+  aid: u32,
+  password: SecretString,
+  created: usize,
+) -> TecResult<()> {
+  let pwd = Pwd {
+    id: 0,
+    aid,
+    password,
+    created,
+  };
+  pwd_repo.add(&pwd)?;
+  Ok(())
+}
@@ -0,0 +1,245 @@
+use std::{
+  cmp, env,
+  fs::File,
+  io::{self, BufRead, BufReader, IsTerminal, Write},
+  os::fd::{FromRawFd, RawFd},
+  path::Path,
+};
+
+use crate::{
+  common::{secret_string, SecretBytes, SecretString, TecError, TecResult},
+  db::{resume_rotation, sqlite_conn, unlock_key, KeyStore},
+  memory,
+  tui::AuthOptions,
+};
+
+// enough for most passwords, so the buffer rarely grows
+const LINE_CAPACITY: usize = 256;
+
+// where the master password is read from
+pub enum PasswordSource {
+  Terminal,
+  // the first line of stdin, when it isn't a terminal
+  Stdin,
+  // the first line of a file descriptor, e.g. `--password-fd 3 3<pwfile`
+  Fd(RawFd),
+  // the name of an environment variable
+  Env(String),
+}
+
+impl PasswordSource {
+  pub fn new(fd: Option<RawFd>, env: Option<String>) -> Self {
+    if let Some(fd) = fd {
+      Self::Fd(fd)
+    } else if let Some(name) = env {
+      Self::Env(name)
+    } else if io::stdin().is_terminal() {
+      Self::Terminal
+    } else {
+      Self::Stdin
+    }
+  }
+
+  pub fn is_terminal(&self) -> bool {
+    matches!(self, Self::Terminal)
+  }
+
+  fn read(&self) -> TecResult<SecretString> {
+    match self {
+      Self::Terminal | Self::Stdin => read_password("password: "),
+      Self::Fd(fd) => {
+        // stdin is read without --password-fd, and stdout or stderr must not be closed
+        if *fd <= libc::STDERR_FILENO {
+          return Err(TecError::InvalidInput(format!(
+            "--password-fd {fd} is a standard stream, use a descriptor from 3"
+          )));
+        }
+        if unsafe { libc::fcntl(*fd, libc::F_GETFD) } == -1 {
+          return Err(TecError::InvalidInput(format!(
+            "--password-fd {fd} is not open - {}",
+            io::Error::last_os_error()
+          )));
+        }
+        // the descriptor is closed once read
+        let file = unsafe { File::from_raw_fd(*fd) };
+        read_line(&mut BufReader::new(file))
+      }
+      Self::Env(name) => {
+        let pwd = SecretString::new(env::var(name)?);
+        // keep it from the commands run by tecpass, e.g. --copy-command
+        env::remove_var(name);
+        Ok(pwd)
+      }
+    }
+  }
+}
+
+// unlock the vault the same way the login screen does, failures count towards the
+// back-off of the login screen
+pub fn unlock(
+  config_path: &Path,
+  opts: &AuthOptions,
+  source: &PasswordSource,
+) -> TecResult<SecretBytes> {
+  let key_path = config_path.join("tecpass.sealed.key");
+  let db_path = config_path.join("tecpass.db");
+
+  let key_store = KeyStore::new(key_path).with_keyfile(opts.keyfile.clone());
+  if !key_store.has_key() {
+    return Err(TecError::NoVault(config_path.display().to_string()));
+  }
+  let conn = sqlite_conn(&db_path)?;
+  resume_rotation(&conn, &key_store)?;
+
+  let mut attempts = key_store.attempts()?;
+  if opts
+    .max_attempts
+    .is_some_and(|max| attempts.failures() >= max)
+  {
+    return Err(TecError::LockedOut);
+  }
+  let remaining = attempts.remaining();
+  if !remaining.is_zero() {
+    return Err(TecError::Throttled(
+      remaining.as_millis().div_ceil(1000) as u64
+    ));
+  }
+
+  let pwd = source.read()?;
+  match unlock_key(&conn, &key_store, pwd.as_bytes()) {
+    Ok(key) => {
+      attempts.reset()?;
+      memory::protect_key(&key);
+      Ok(key)
+    }
+    // only a key that fails to decrypt is a wrong password
+    Err(TecError::AESError(_)) => {
+      attempts.fail()?;
+      Err(TecError::WrongPassword)
+    }
+    Err(err) => Err(err),
+  }
+}
+
+// read a line from stdin, without echoing it when stdin is a terminal
+pub fn read_password(prompt: &str) -> TecResult<SecretString> {
+  let stdin = io::stdin();
+  if !stdin.is_terminal() {
+    return read_line(&mut stdin.lock());
+  }
+
+  eprint!("{prompt}");
+  io::stderr().flush()?;
+  let echo = Echo::disable()?;
+  let res = read_line(&mut stdin.lock());
+  drop(echo);
+  eprintln!();
+  res
+}
+
+fn read_line(reader: &mut impl BufRead) -> TecResult<SecretString> {
+  let mut line = SecretBytes::new(Vec::with_capacity(LINE_CAPACITY));
+  loop {
+    let buf = reader.fill_buf()?;
+    if buf.is_empty() {
+      break;
+    }
+    let end = buf.iter().position(|&b| b == b'\n');
+    let chunk = &buf[..end.unwrap_or(buf.len())];
+    reserve(&mut line, chunk.len());
+    line.extend_from_slice(chunk);
+    let used = chunk.len() + usize::from(end.is_some());
+    reader.consume(used);
+    if end.is_some() {
+      break;
+    }
+  }
+  if line.last() == Some(&b'\r') {
+    line.pop();
+  }
+  secret_string(line)
+}
+
+// grow the buffer by hand and wipe the old one, as `read_line` of `BufRead` would leave
+// parts of the password behind in freed memory
+fn reserve(line: &mut SecretBytes, additional: usize) {
+  let required = line.len() + additional;
+  if required > line.capacity() {
+    let mut grown = Vec::with_capacity(cmp::max(required, line.capacity() * 2));
+    grown.extend_from_slice(line);
+    *line = SecretBytes::new(grown);
+  }
+}
+
+// turns the echo of the terminal back on when dropped
+struct Echo(libc::termios);
+
+impl Echo {
+  fn disable() -> TecResult<Self> {
+    let mut term = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut term) } != 0 {
+      return Err(io::Error::last_os_error())?;
+    }
+    let orig = term;
+    term.c_lflag &= !libc::ECHO;
+    if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term) } != 0 {
+      return Err(io::Error::last_os_error())?;
+    }
+    Ok(Self(orig))
+  }
+}
+
+impl Drop for Echo {
+  fn drop(&mut self) {
+    unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, io::Cursor, process};
+
+  use super::*;
+  use crate::{cipher::Argon2Params, tui::LengthLimits};
+
+  #[test]
+  fn reads_the_first_line() {
+    let long = "x".repeat(LINE_CAPACITY * 3);
+    let mut input = Cursor::new(format!("{long}\r\nnext\n"));
+    assert_eq!(*read_line(&mut input).unwrap(), long);
+    assert_eq!(*read_line(&mut input).unwrap(), "next");
+    assert_eq!(*read_line(&mut input).unwrap(), "");
+  }
+
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn not_dumpable_after_unlock() {
+    // dumpable again, in case another test has already disabled it
+    unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0) };
+
+    let dir = env::temp_dir().join(format!("tecpass-unlock-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let params = Argon2Params {
+      m_cost: 8,
+      t_cost: 1,
+      p_cost: 1,
+      ..Default::default()
+    };
+    KeyStore::new(dir.join("tecpass.sealed.key"))
+      .seal_key_with(&[7; 32], b"password1", params)
+      .unwrap();
+    env::set_var("TECPASS_TEST_PASSWORD", "password1");
+    let opts = AuthOptions {
+      keyfile: None,
+      max_attempts: None,
+      master_length: LengthLimits::MASTER_PASSWORD,
+    };
+    let source = PasswordSource::Env("TECPASS_TEST_PASSWORD".to_owned());
+    let key = unlock(&dir, &opts, &source);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(*key.unwrap(), [7; 32]);
+    assert_eq!(unsafe { libc::prctl(libc::PR_GET_DUMPABLE, 0, 0, 0, 0) }, 0);
+    assert!(!memory::is_dumpable());
+  }
+}
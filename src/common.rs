@@ -1,5 +1,11 @@
 use std::string::FromUtf8Error;
 
+use zeroize::Zeroizing;
+
+// secrets are wiped from memory when dropped
+pub type SecretBytes = Zeroizing<Vec<u8>>;
+pub type SecretString = Zeroizing<String>;
+
 #[derive(thiserror::Error, Debug)]
 pub enum TecError {
   #[error("invalid length - {0}")]
@@ -18,18 +24,71 @@ pub enum TecError {
   SqliteError(#[from] rusqlite::Error),
   #[error("csv error - {0}")]
   CSVError(#[from] csv::Error),
+  #[error("json error - {0}")]
+  JSONError(#[from] serde_json::Error),
   #[error("from utf8 error - {0}")]
   FromUtf8Error(#[from] FromUtf8Error),
+  #[error("utf8 error - {0}")]
+  Utf8Error(#[from] std::str::Utf8Error),
   #[error("var error - {0}")]
   VarError(#[from] std::env::VarError),
   #[error("clipboard paste error - {0}")]
   ClipboardPasteError(#[from] wl_clipboard_rs::paste::Error),
   #[error("clipboard copy error - {0}")]
   ClipboardCopyError(#[from] wl_clipboard_rs::copy::Error),
-  // #[error("invalid input")]
-  // InvalidInput,
+  #[error("x11 clipboard error - {0}")]
+  X11ClipboardError(#[from] x11_clipboard::error::Error),
+  #[error("clipboard error - {0}")]
+  Clipboard(String),
+  #[error("invalid key file - {0}")]
+  InvalidKeyFile(String),
+  #[error("ciphertext does not belong to {0}")]
+  InvalidCiphertext(String),
+  #[error("the vault has been tampered with, run `tecpass --verify` for details")]
+  Tampered,
+  #[error("the vault requires a keyfile, pass it with --keyfile")]
+  KeyfileRequired,
+  #[error("keyfile not found - {0}")]
+  KeyfileNotFound(String),
+  #[error("wrong keyfile")]
+  WrongKeyfile,
+  #[error("the vault is locked")]
+  Locked,
+  #[error("no vault in {0}, run tecpass to create one")]
+  NoVault(String),
+  #[error("wrong password")]
+  WrongPassword,
+  #[error("too many failed attempts, try again in {0}s")]
+  Throttled(u64),
+  #[error("too many failed attempts, use the recovery code")]
+  LockedOut,
+  #[error("no account matches {0}")]
+  AccountNotFound(String),
+  #[error("several accounts match {0}")]
+  AmbiguousAccount(String),
+  #[error("the recovery code belongs to a previous vault key")]
+  StaleRecovery,
+  #[error("invalid input - {0}")]
+  InvalidInput(String),
+  #[error("invalid hash list - {0}")]
+  InvalidHashList(String),
+  #[error("{0} accounts have weak, breached, reused or old passwords")]
+  IssuesFound(usize),
   // #[error("password not match")]
   // PasswordNotMatch,
 }
 
 pub type TecResult<T> = Result<T, TecError>;
+
+// take over the buffer of decrypted bytes without leaving a copy behind
+pub fn secret_string(mut bytes: SecretBytes) -> TecResult<SecretString> {
+  let bytes = std::mem::take(&mut *bytes);
+  match String::from_utf8(bytes) {
+    Ok(s) => Ok(Zeroizing::new(s)),
+    Err(err) => {
+      let utf8_error = err.utf8_error();
+      drop(Zeroizing::new(err.into_bytes()));
+      Err(utf8_error)?
+    }
+  }
+}
@@ -0,0 +1,86 @@
+use std::{
+  fs,
+  path::PathBuf,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::common::{TecError, TecResult};
+
+use super::key::write_atomic;
+
+// failed logins allowed in a row before the back-off kicks in
+const FREE_ATTEMPTS: u32 = 3;
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/*
+Failed logins are counted in `tecpass.sealed.key.attempts`, a single line holding the
+number of failures in a row and the unix time in ms of the last one:
+  5 1718000000000
+After `FREE_ATTEMPTS` failures, each attempt has to wait twice as long as the previous
+one, up to `MAX_BACKOFF`. The file is removed on a successful login.
+*/
+pub struct Attempts {
+  path: PathBuf,
+  failures: u32,
+  last_failure: u64,
+}
+
+impl Attempts {
+  pub fn load(path: PathBuf) -> TecResult<Self> {
+    let mut attempts = Self {
+      path,
+      failures: 0,
+      last_failure: 0,
+    };
+    if attempts.path.exists() {
+      let content = fs::read_to_string(&attempts.path)?;
+      let Some((failures, last_failure)) = content.trim().split_once(' ') else {
+        return Err(TecError::InvalidKeyFile("malformed attempts file".into()));
+      };
+      attempts.failures = failures.parse()?;
+      attempts.last_failure = last_failure.parse()?;
+    }
+    Ok(attempts)
+  }
+
+  pub fn failures(&self) -> u32 {
+    self.failures
+  }
+
+  // how long to wait before the next attempt is allowed
+  pub fn remaining(&self) -> Duration {
+    let elapsed = Duration::from_millis(now_millis().saturating_sub(self.last_failure));
+    backoff(self.failures).saturating_sub(elapsed)
+  }
+
+  pub fn fail(&mut self) -> TecResult<()> {
+    self.failures += 1;
+    self.last_failure = now_millis();
+    let content = format!("{} {}", self.failures, self.last_failure);
+    write_atomic(&self.path, content.as_bytes())
+  }
+
+  pub fn reset(&mut self) -> TecResult<()> {
+    self.failures = 0;
+    self.last_failure = 0;
+    if self.path.exists() {
+      fs::remove_file(&self.path)?;
+    }
+    Ok(())
+  }
+}
+
+fn backoff(failures: u32) -> Duration {
+  if failures < FREE_ATTEMPTS {
+    return Duration::ZERO;
+  }
+  let exp = (failures - FREE_ATTEMPTS).min(12);
+  Duration::from_secs(1 << exp).min(MAX_BACKOFF)
+}
+
+fn now_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .expect("Clock may have gone backwards")
+    .as_millis() as u64
+}
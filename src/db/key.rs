@@ -1,20 +1,70 @@
 use std::{
-  fs::File,
-  io::{Read, Write},
-  path::Path,
+  fmt,
+  fs::{self, File, OpenOptions},
+  io::{ErrorKind, Read, Write},
+  os::unix::fs::OpenOptionsExt,
+  path::{Path, PathBuf},
 };
 
+use argon2::Algorithm;
 use rand::{rngs::OsRng, RngCore};
+use sha3::{Digest, Sha3_256};
+use zeroize::Zeroizing;
 
-use crate::{cipher::Argon2Cipher, common::TecResult};
+use crate::{
+  cipher::{Argon2Cipher, Argon2Params},
+  common::{SecretBytes, TecError, TecResult},
+  hex,
+};
+
+use super::Attempts;
+
+/*
+The sealed key file is a single line:
+  $tecpass$v=2$argon2id$m=19456,t=2,p=1,s=32$<hex of salt || nonce || ciphertext>
+
+Since v=2 the sealed payload is the key followed by a byte of flags, so far only whether
+the vault has a manifest. Sealed along with the key, it can't be forged or removed
+without the password, unlike anything kept in the database or next to it.
+
+If a keyfile is required, the digest of its contents is passed to argon2 as the secret
+input, and a check of the digest is stored before the hex part, so that a missing or
+wrong keyfile can be told apart from a wrong password:
+  $tecpass$v=2$argon2id$m=19456,t=2,p=1,s=32$kf=<hex check>$<hex ...>
+
+Files written before the format was versioned hold only the hex part, sealed with the
+default argon2 parameters. They, and v=1 files, are rewritten in the current format on
+the next login.
+*/
+const KEY_FORMAT: &str = "tecpass";
+const KEY_VERSION: &str = "v=2";
+const KEY_VERSIONS: [&str; 2] = ["v=1", KEY_VERSION];
+const KEY_LEN: usize = 32;
+// the vault has a manifest, set on every key sealed since v=2
+const FLAG_MANIFEST: u8 = 1;
+// crockford base32, without the letters easily mistaken for digits
+const RECOVERY_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const RECOVERY_LEN: usize = 20;
+const KEYFILE_LEN: usize = 64;
+const KEYFILE_CHECK_LEN: usize = 8;
 
 pub struct KeyStore<P: AsRef<Path>> {
   path: P,
+  keyfile: Option<PathBuf>,
 }
 
 impl<P: AsRef<Path>> KeyStore<P> {
   pub fn new(path: P) -> Self {
-    Self { path }
+    Self {
+      path,
+      keyfile: None,
+    }
+  }
+
+  // require the keyfile in addition to the password whenever the key is sealed
+  pub fn with_keyfile(mut self, keyfile: Option<PathBuf>) -> Self {
+    self.keyfile = keyfile;
+    self
   }
 
   pub fn has_key(&self) -> bool {
@@ -22,24 +72,395 @@ impl<P: AsRef<Path>> KeyStore<P> {
     // self.path.exists()
   }
 
-  pub fn set_key(&self, pwd: &[u8]) -> TecResult<()> {
-    let mut key = [0u8; 32];
+  pub fn set_key(&self, pwd: &[u8]) -> TecResult<SecretBytes> {
+    let mut key = Zeroizing::new(vec![0u8; KEY_LEN]);
     OsRng.fill_bytes(&mut key);
+    self.seal_key(&key, pwd)?;
+    Ok(key)
+  }
 
-    let cipher = Argon2Cipher::new(pwd);
-    let enc = cipher.encrypt(&key)?;
-    let mut file = File::create(&self.path)?;
-    file.write_all(enc.as_bytes())?;
-    Ok(())
+  // the key, and whether it was sealed since the vault has a manifest. a key sealed
+  // before should be `reseal`ed once the vault is signed.
+  pub fn get_key(&self, pwd: &[u8]) -> TecResult<(SecretBytes, bool)> {
+    let sealed = self.sealed()?;
+    let secret = self.secret_of(&sealed)?;
+    let cipher = Argon2Cipher::new(pwd)
+      .with_params(sealed.params)
+      .with_secret(&secret);
+    unseal(&cipher, &sealed.enc)
+  }
+
+  // seal the key again with the parameters and keyfile it is sealed with, to bring it to
+  // the current format
+  pub fn reseal(&self, key: &[u8], pwd: &[u8]) -> TecResult<()> {
+    let sealed = self.sealed()?;
+    let secret = self.secret_of(&sealed)?;
+    let secret = sealed.keyfile.is_some().then_some(secret.as_slice());
+    let sealed = SealedKey::seal(key, pwd, sealed.params, secret)?;
+    write_atomic(self.path.as_ref(), sealed.to_string().as_bytes())
   }
 
-  pub fn get_key(&self, pwd: &[u8]) -> TecResult<Vec<u8>> {
+  fn sealed(&self) -> TecResult<SealedKey> {
     let mut file = File::open(&self.path)?;
-    let mut enc = String::new();
-    file.read_to_string(&mut enc)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    SealedKey::parse(&content)
+  }
 
-    let cipher = Argon2Cipher::new(pwd);
-    let key = cipher.decrypt(&enc)?;
-    Ok(key)
+  // the digest of the keyfile the key is sealed with, empty if it isn't
+  fn secret_of(&self, sealed: &SealedKey) -> TecResult<SecretBytes> {
+    let secret = match &sealed.keyfile {
+      Some(check) => {
+        let secret = self
+          .keyfile_secret(false)?
+          .ok_or(TecError::KeyfileRequired)?;
+        if keyfile_check(&secret)? != *check {
+          return Err(TecError::WrongKeyfile);
+        }
+        secret
+      }
+      None => SecretBytes::default(),
+    };
+    Ok(secret)
+  }
+
+  // the argon2 parameters the key is currently sealed with
+  pub fn params(&self) -> TecResult<Argon2Params> {
+    if !self.has_key() {
+      return Ok(Argon2Params::default());
+    }
+    let content = fs::read_to_string(&self.path)?;
+    Ok(SealedKey::parse(&content)?.params)
+  }
+
+  // seal an existing key under a (new) password, the sealed file is replaced atomically
+  // so that a crash can never leave a half written key behind
+  pub fn seal_key(&self, key: &[u8], pwd: &[u8]) -> TecResult<()> {
+    self.seal_key_with(key, pwd, self.params()?)
+  }
+
+  pub fn seal_key_with(&self, key: &[u8], pwd: &[u8], params: Argon2Params) -> TecResult<()> {
+    let secret = self.keyfile_secret(true)?;
+    let sealed = SealedKey::seal(key, pwd, params, secret.as_deref().map(|s| s.as_slice()))?;
+    write_atomic(self.path.as_ref(), sealed.to_string().as_bytes())
+  }
+
+  // seal a second copy of the key under a random recovery code, to be used in case the
+  // password is forgotten. the code is returned to be shown to the user once. the copy
+  // does not need the keyfile, so that the vault can still be opened if it is lost. it
+  // is stored along with the fingerprint of the key, like the pending key.
+  pub fn set_recovery(&self, key: &[u8]) -> TecResult<String> {
+    let mut bytes = Zeroizing::new([0u8; RECOVERY_LEN]);
+    OsRng.fill_bytes(&mut *bytes);
+    let code = encode_recovery(&*bytes);
+
+    let code_bytes = normalize_recovery(&code);
+    let sealed = SealedKey::seal(key, code_bytes.as_bytes(), self.params()?, None)?;
+    let content = format!("{}\n{}", fingerprint(key)?, sealed);
+    write_atomic(&self.recovery_path(), content.as_bytes())?;
+    Ok(code)
+  }
+
+  pub fn has_recovery(&self) -> bool {
+    self.recovery_path().exists()
+  }
+
+  // the failed logins in a row, counted next to the sealed key
+  pub fn attempts(&self) -> TecResult<Attempts> {
+    let mut path = self.path.as_ref().as_os_str().to_owned();
+    path.push(".attempts");
+    Attempts::load(path.into())
+  }
+
+  // `key_id` is the fingerprint of the vault key recorded by the last rotation, a copy of
+  // any other key would seal the wrong key under the new password. copies written before
+  // the fingerprint was stored are checked once decrypted.
+  pub fn recover_key(&self, code: &str, key_id: Option<&str>) -> TecResult<(SecretBytes, bool)> {
+    let content = fs::read_to_string(self.recovery_path())?;
+    let (stored_id, sealed) = match content.trim().split_once('\n') {
+      Some((stored_id, sealed)) => (Some(stored_id), sealed),
+      None => (None, content.as_str()),
+    };
+    let is_stale = |id: &str| key_id.is_some_and(|key_id| key_id != id);
+    if stored_id.is_some_and(is_stale) {
+      return Err(TecError::StaleRecovery);
+    }
+
+    let sealed = SealedKey::parse(sealed)?;
+    let code = normalize_recovery(code);
+    let cipher = Argon2Cipher::new(code.as_bytes()).with_params(sealed.params);
+    let (key, has_manifest) = unseal(&cipher, &sealed.enc)?;
+    if is_stale(&fingerprint(&key)?) {
+      return Err(TecError::StaleRecovery);
+    }
+    Ok((key, has_manifest))
+  }
+
+  // a pending key is sealed next to the current one while the vault is being rotated,
+  // together with the fingerprint of the new key, so that an interrupted rotation can be
+  // finished or discarded without knowing the password
+  pub fn seal_pending(&self, key: &[u8], pwd: &[u8]) -> TecResult<()> {
+    let secret = self.keyfile_secret(true)?;
+    let sealed = SealedKey::seal(
+      key,
+      pwd,
+      self.params()?,
+      secret.as_deref().map(|s| s.as_slice()),
+    )?;
+    let content = format!("{}\n{}", fingerprint(key)?, sealed);
+    write_atomic(&self.pending_path(), content.as_bytes())
+  }
+
+  pub fn pending_fingerprint(&self) -> TecResult<Option<String>> {
+    let path = self.pending_path();
+    if !path.exists() {
+      return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    Ok(content.lines().next().map(|l| l.to_string()))
+  }
+
+  // the recovery copy still holds the old key, and is removed along with the pending
+  // key. a new code has to be set up after the rotation.
+  pub fn commit_pending(&self) -> TecResult<()> {
+    let path = self.pending_path();
+    let content = fs::read_to_string(&path)?;
+    if let Some(enc) = content.lines().nth(1) {
+      write_atomic(self.path.as_ref(), enc.as_bytes())?;
+    }
+    if self.has_recovery() {
+      fs::remove_file(self.recovery_path())?;
+    }
+    fs::remove_file(path)?;
+    Ok(())
+  }
+
+  pub fn discard_pending(&self) -> TecResult<()> {
+    let path = self.pending_path();
+    if path.exists() {
+      fs::remove_file(path)?;
+    }
+    Ok(())
+  }
+
+  fn pending_path(&self) -> PathBuf {
+    let mut path = self.path.as_ref().as_os_str().to_owned();
+    path.push(".pending");
+    path.into()
+  }
+
+  fn recovery_path(&self) -> PathBuf {
+    let mut path = self.path.as_ref().as_os_str().to_owned();
+    path.push(".recovery");
+    path.into()
+  }
+
+  // the digest of the keyfile contents, a missing keyfile is filled with random bytes
+  // if `create` is set, i.e. when the key is being sealed
+  fn keyfile_secret(&self, create: bool) -> TecResult<Option<SecretBytes>> {
+    let Some(path) = &self.keyfile else {
+      return Ok(None);
+    };
+    if create && !path.exists() {
+      let mut bytes = Zeroizing::new([0u8; KEYFILE_LEN]);
+      OsRng.fill_bytes(&mut *bytes);
+      let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+      file.write_all(&*bytes)?;
+      file.sync_all()?;
+    }
+
+    let content = match fs::read(path) {
+      Ok(content) => Zeroizing::new(content),
+      Err(err) if err.kind() == ErrorKind::NotFound => {
+        return Err(TecError::KeyfileNotFound(path.display().to_string()))
+      }
+      Err(err) => return Err(err.into()),
+    };
+    let mut hasher = Sha3_256::new();
+    hasher.update(&*content);
+    Ok(Some(Zeroizing::new(hasher.finalize().to_vec())))
+  }
+}
+
+struct SealedKey {
+  params: Argon2Params,
+  // check of the keyfile digest, if one is required
+  keyfile: Option<String>,
+  enc: String,
+}
+
+impl SealedKey {
+  fn seal(key: &[u8], pwd: &[u8], params: Argon2Params, secret: Option<&[u8]>) -> TecResult<Self> {
+    let cipher = Argon2Cipher::new(pwd)
+      .with_params(params)
+      .with_secret(secret.unwrap_or_default());
+    let payload = Zeroizing::new([key, &[FLAG_MANIFEST]].concat());
+    Ok(Self {
+      params,
+      keyfile: secret.map(keyfile_check).transpose()?,
+      enc: cipher.encrypt(&payload)?,
+    })
+  }
+
+  fn parse(content: &str) -> TecResult<Self> {
+    let content = content.trim();
+    if !content.starts_with('$') {
+      return Ok(Self {
+        params: Argon2Params::default(),
+        keyfile: None,
+        enc: content.to_string(),
+      });
+    }
+
+    let parts: Vec<&str> = content.split('$').collect();
+    let (format, version, algorithm, costs, keyfile, enc) = match parts[..] {
+      [_, format, version, algorithm, costs, enc] => (format, version, algorithm, costs, None, enc),
+      [_, format, version, algorithm, costs, keyfile, enc] => {
+        let check = keyfile
+          .strip_prefix("kf=")
+          .ok_or_else(|| TecError::InvalidKeyFile(format!("unknown field {keyfile}")))?;
+        (
+          format,
+          version,
+          algorithm,
+          costs,
+          Some(check.to_string()),
+          enc,
+        )
+      }
+      _ => return Err(TecError::InvalidKeyFile("malformed header".into())),
+    };
+    if format != KEY_FORMAT {
+      return Err(TecError::InvalidKeyFile(format!("unknown format {format}")));
+    }
+    if !KEY_VERSIONS.contains(&version) {
+      return Err(TecError::InvalidKeyFile(format!(
+        "unsupported version {version}"
+      )));
+    }
+
+    let algorithm =
+      Algorithm::new(algorithm).map_err(|_| TecError::InvalidKeyFile(algorithm.into()))?;
+    let mut params = Argon2Params {
+      algorithm,
+      ..Argon2Params::default()
+    };
+    for cost in costs.split(',') {
+      match cost.split_once('=') {
+        Some(("m", v)) => params.m_cost = v.parse()?,
+        Some(("t", v)) => params.t_cost = v.parse()?,
+        Some(("p", v)) => params.p_cost = v.parse()?,
+        Some(("s", v)) => params.salt_len = v.parse()?,
+        _ => {
+          return Err(TecError::InvalidKeyFile(format!(
+            "unknown parameter {cost}"
+          )))
+        }
+      }
+    }
+
+    Ok(Self {
+      params,
+      keyfile,
+      enc: enc.to_string(),
+    })
+  }
+}
+
+impl fmt::Display for SealedKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let p = &self.params;
+    write!(
+      f,
+      "${KEY_FORMAT}${KEY_VERSION}${}$m={},t={},p={},s={}",
+      p.algorithm, p.m_cost, p.t_cost, p.p_cost, p.salt_len
+    )?;
+    if let Some(check) = &self.keyfile {
+      write!(f, "$kf={check}")?;
+    }
+    write!(f, "${}", self.enc)
+  }
+}
+
+// the key and whether the vault has a manifest, from the payload of any version
+fn unseal(cipher: &Argon2Cipher, enc: &str) -> TecResult<(SecretBytes, bool)> {
+  let mut payload = cipher.decrypt(enc)?;
+  let flags = match payload.len() {
+    KEY_LEN => 0,
+    len if len == KEY_LEN + 1 => payload[KEY_LEN],
+    len => {
+      return Err(TecError::InvalidKeyFile(format!(
+        "sealed payload of {len} bytes"
+      )))
+    }
+  };
+  payload.truncate(KEY_LEN);
+  Ok((payload, flags & FLAG_MANIFEST != 0))
+}
+
+// groups of 4 characters, e.g. 7K2M-Q9XD-...
+fn encode_recovery(bytes: &[u8]) -> String {
+  let mut code = String::new();
+  let mut acc: u32 = 0;
+  let mut bits = 0;
+  for &b in bytes {
+    acc = (acc << 8) | b as u32;
+    bits += 8;
+    while bits >= 5 {
+      bits -= 5;
+      if !code.is_empty() && code.len() % 5 == 4 {
+        code.push('-');
+      }
+      code.push(RECOVERY_ALPHABET[((acc >> bits) & 0x1f) as usize] as char);
+    }
+  }
+  code
+}
+
+// accept the code in any case, with or without separators
+fn normalize_recovery(code: &str) -> Zeroizing<String> {
+  Zeroizing::new(
+    code
+      .chars()
+      .filter(|c| c.is_ascii_alphanumeric())
+      .map(|c| c.to_ascii_uppercase())
+      .collect(),
+  )
+}
+
+// tells whether the right keyfile is given before running argon2, without revealing it
+fn keyfile_check(secret: &[u8]) -> TecResult<String> {
+  let mut hasher = Sha3_256::new();
+  hasher.update(b"tecpass-keyfile");
+  hasher.update(secret);
+  hex::encode(&hasher.finalize()[..KEYFILE_CHECK_LEN])
+}
+
+// identifies a vault key without revealing it
+pub fn fingerprint(key: &[u8]) -> TecResult<String> {
+  let mut hasher = Sha3_256::new();
+  hasher.update(b"tecpass-key-id");
+  hasher.update(key);
+  hex::encode(&hasher.finalize())
+}
+
+pub(super) fn write_atomic(path: &Path, content: &[u8]) -> TecResult<()> {
+  let mut tmp_path = path.as_os_str().to_owned();
+  tmp_path.push(".tmp");
+
+  let mut file = File::create(&tmp_path)?;
+  file.write_all(content)?;
+  file.sync_all()?;
+  fs::rename(&tmp_path, path)?;
+
+  if let Some(dir) = path.parent() {
+    if let Ok(dir) = File::open(dir) {
+      dir.sync_all()?;
+    }
   }
+  Ok(())
 }
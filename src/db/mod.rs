@@ -1,5 +1,11 @@
+mod attempts;
 mod key;
+mod rotate;
 mod sqlite;
+mod unlock;
 
-pub use self::key::KeyStore;
+pub use self::attempts::Attempts;
+pub use self::key::{fingerprint, KeyStore};
+pub use self::rotate::{key_id, resume_rotation, rotate_key};
 pub use self::sqlite::sqlite_conn;
+pub use self::unlock::{recover_key, unlock_key};
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use rand::{rngs::OsRng, RngCore};
+use rusqlite::{Connection, OptionalExtension};
+
+use zeroize::Zeroizing;
+
+use crate::{
+  cipher::AesCipher,
+  common::TecError,
+  common::{SecretBytes, TecResult},
+  repo::{migrate, rebuild_manifest, reencrypt, verify_manifest},
+};
+
+use super::{fingerprint, KeyStore};
+
+const KEY_ID: &str = "key_id";
+
+/*
+Rotating the vault key:
+  1. the new key is sealed into `tecpass.sealed.key.pending` along with its fingerprint
+  2. every account and pwd row is re-encrypted, and the fingerprint of the new key is
+     recorded in `meta`, all in a single transaction
+  3. the pending key replaces the sealed key, and the recovery copy of the old key is
+     removed
+
+If the process dies before 2 commits, sqlite rolls the rows back and the pending key is
+discarded on the next start. If it dies after, the pending key is promoted instead.
+*/
+pub fn rotate_key<P: AsRef<Path>>(
+  conn: &mut Connection,
+  key_store: &KeyStore<P>,
+  key: &[u8],
+  pwd: &[u8],
+) -> TecResult<SecretBytes> {
+  resume_rotation(conn, key_store)?;
+
+  let mut new_key = Zeroizing::new(vec![0u8; 32]);
+  OsRng.fill_bytes(&mut new_key);
+  key_store.seal_pending(&new_key, pwd)?;
+
+  let old_cipher = AesCipher::from_slice(key)?;
+  let new_cipher = AesCipher::from_slice(&new_key)?;
+
+  // re-keying would sign whatever was tampered with as genuine
+  if !verify_manifest(conn, key)?.is_empty() {
+    key_store.discard_pending()?;
+    return Err(TecError::Tampered);
+  }
+  migrate(conn, &old_cipher)?;
+  let tx = conn.transaction()?;
+  reencrypt(&tx, &old_cipher, &new_cipher)?;
+  rebuild_manifest(&tx, &new_key)?;
+  tx.execute(
+    "INSERT OR REPLACE INTO meta (name, value) VALUES (?1, ?2)",
+    (KEY_ID, fingerprint(&new_key)?),
+  )?;
+  tx.commit()?;
+
+  key_store.commit_pending()?;
+  Ok(new_key)
+}
+
+// finish or discard a rotation that was interrupted
+pub fn resume_rotation<P: AsRef<Path>>(
+  conn: &Connection,
+  key_store: &KeyStore<P>,
+) -> TecResult<()> {
+  if let Some(pending_id) = key_store.pending_fingerprint()? {
+    if key_id(conn)?.as_deref() == Some(pending_id.as_str()) {
+      key_store.commit_pending()?;
+    } else {
+      key_store.discard_pending()?;
+    }
+  }
+  Ok(())
+}
+
+// the fingerprint of the vault key, recorded by the last rotation. `None` if the key has
+// never been rotated.
+pub fn key_id(conn: &Connection) -> TecResult<Option<String>> {
+  let key_id = conn
+    .query_row("SELECT value FROM meta WHERE name = ?1", [KEY_ID], |row| {
+      row.get(0)
+    })
+    .optional()?;
+  Ok(key_id)
+}
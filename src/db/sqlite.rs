@@ -29,5 +29,25 @@ pub fn sqlite_conn<P: AsRef<Path>>(path: P) -> TecResult<Connection> {
     "#,
     (),
   )?;
+  conn.execute(
+    r#"
+    CREATE TABLE if not exists manifest (
+        tbl TEXT NOT NULL,
+        rid INTEGER NOT NULL,
+        mac BLOB NOT NULL,
+        PRIMARY KEY (tbl, rid)
+    );
+    "#,
+    (),
+  )?;
+  conn.execute(
+    r#"
+    CREATE TABLE if not exists meta (
+        name TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    "#,
+    (),
+  )?;
   Ok(conn)
 }
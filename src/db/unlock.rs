@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::{
+  common::{SecretBytes, TecResult},
+  repo::init_manifest,
+};
+
+use super::{key_id, KeyStore};
+
+/*
+A key sealed before the vault had a manifest, e.g. by an older version, is trusted once:
+the vault is signed as it is, and the key is sealed again along with the flag that it
+now has a manifest. From then on a database without one is reported as tampered.
+*/
+pub fn unlock_key<P: AsRef<Path>>(
+  conn: &Connection,
+  key_store: &KeyStore<P>,
+  pwd: &[u8],
+) -> TecResult<SecretBytes> {
+  let (key, has_manifest) = key_store.get_key(pwd)?;
+  if !has_manifest {
+    init_manifest(conn, &key)?;
+    // best effort, the key is unlocked even if the upgrade can't be written, e.g. to a
+    // read-only config directory
+    if let Err(err) = key_store.reseal(&key, pwd) {
+      eprintln!("warning: could not upgrade the sealed key - {err}");
+    }
+  }
+  Ok(key)
+}
+
+// a recovery copy sealed before the vault had a manifest is trusted the same way, the key
+// is then sealed under a new password along with the flag
+pub fn recover_key<P: AsRef<Path>>(
+  conn: &Connection,
+  key_store: &KeyStore<P>,
+  code: &str,
+) -> TecResult<SecretBytes> {
+  let key_id = key_id(conn)?;
+  let (key, has_manifest) = key_store.recover_key(code, key_id.as_deref())?;
+  if !has_manifest {
+    init_manifest(conn, &key)?;
+  }
+  Ok(key)
+}
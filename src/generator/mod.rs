@@ -0,0 +1,211 @@
+use rand::{rngs::OsRng, seq::SliceRandom};
+
+use crate::common::{SecretString, TecError, TecResult};
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{}~";
+// easily mistaken for one another when read or typed by hand
+const AMBIGUOUS: &str = "0Oo1lI|";
+
+// the english wordlist of BIP-39, 2048 words or 11 bits of entropy per word
+pub(crate) const WORDLIST: &str = include_str!("wordlist.txt");
+
+#[derive(Debug, Clone)]
+pub struct Policy {
+  pub length: usize,
+  pub lower: bool,
+  pub upper: bool,
+  pub digits: bool,
+  pub symbols: bool,
+  pub exclude_ambiguous: bool,
+  // every enabled class appears at least once
+  pub require_each: bool,
+}
+
+impl Default for Policy {
+  fn default() -> Self {
+    Self {
+      length: 20,
+      lower: true,
+      upper: true,
+      digits: true,
+      symbols: true,
+      exclude_ambiguous: false,
+      require_each: true,
+    }
+  }
+}
+
+impl Policy {
+  fn classes(&self) -> Vec<Vec<char>> {
+    [
+      (self.lower, LOWER),
+      (self.upper, UPPER),
+      (self.digits, DIGITS),
+      (self.symbols, SYMBOLS),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, class)| {
+      class
+        .chars()
+        .filter(|c| !(self.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+        .collect()
+    })
+    .collect()
+  }
+}
+
+pub fn generate(policy: &Policy) -> TecResult<SecretString> {
+  let classes = policy.classes();
+  if classes.is_empty() {
+    return Err(TecError::InvalidInput(
+      "no character class is enabled".into(),
+    ));
+  }
+  if policy.require_each && policy.length < classes.len() {
+    return Err(TecError::InvalidInput(format!(
+      "the length has to be at least {} to contain every class",
+      classes.len()
+    )));
+  }
+
+  let all: Vec<char> = classes.concat();
+  let mut chars = Vec::with_capacity(policy.length);
+  if policy.require_each {
+    for class in classes.iter() {
+      chars.push(*class.choose(&mut OsRng).unwrap());
+    }
+  }
+  while chars.len() < policy.length {
+    chars.push(*all.choose(&mut OsRng).unwrap());
+  }
+  // the required characters would otherwise always come first
+  chars.shuffle(&mut OsRng);
+
+  let mut pwd = SecretString::new(String::with_capacity(policy.length * 4));
+  pwd.extend(chars.iter());
+  chars.fill('\0');
+  Ok(pwd)
+}
+
+// diceware-style, e.g. `margin-tunnel-coach-ripple-oyster-lend`
+pub fn passphrase(words: usize, separator: &str) -> TecResult<SecretString> {
+  if words == 0 {
+    return Err(TecError::InvalidInput("a passphrase needs a word".into()));
+  }
+  let wordlist: Vec<&str> = WORDLIST.lines().collect();
+  // no word is longer than 8 letters, so the string never has to grow and leave a copy
+  let mut pwd = SecretString::new(String::with_capacity(words * (8 + separator.len())));
+  for i in 0..words {
+    if i > 0 {
+      pwd.push_str(separator);
+    }
+    pwd.push_str(wordlist.choose(&mut OsRng).unwrap());
+  }
+  Ok(pwd)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn has_any(pwd: &str, class: &str) -> bool {
+    pwd.chars().any(|c| class.contains(c))
+  }
+
+  #[test]
+  fn honors_the_length() {
+    for length in [4, 20, 64] {
+      let pwd = generate(&Policy {
+        length,
+        ..Default::default()
+      })
+      .unwrap();
+      assert_eq!(pwd.chars().count(), length);
+    }
+  }
+
+  #[test]
+  fn requires_each_class() {
+    // with one character per class, a missing class would show up quickly
+    let policy = Policy {
+      length: 4,
+      ..Default::default()
+    };
+    for _ in 0..200 {
+      let pwd = generate(&policy).unwrap();
+      for class in [LOWER, UPPER, DIGITS, SYMBOLS] {
+        assert!(has_any(&pwd, class), "{} lacks one of {class}", *pwd);
+      }
+    }
+  }
+
+  #[test]
+  fn keeps_to_the_enabled_classes() {
+    let policy = Policy {
+      upper: false,
+      symbols: false,
+      ..Default::default()
+    };
+    for _ in 0..50 {
+      let pwd = generate(&policy).unwrap();
+      assert!(pwd.chars().all(|c| LOWER.contains(c) || DIGITS.contains(c)));
+    }
+  }
+
+  #[test]
+  fn excludes_ambiguous() {
+    let policy = Policy {
+      length: 64,
+      exclude_ambiguous: true,
+      ..Default::default()
+    };
+    for _ in 0..50 {
+      let pwd = generate(&policy).unwrap();
+      assert!(
+        !has_any(&pwd, AMBIGUOUS),
+        "{} has an ambiguous character",
+        *pwd
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_impossible_policies() {
+    let none = Policy {
+      lower: false,
+      upper: false,
+      digits: false,
+      symbols: false,
+      ..Default::default()
+    };
+    assert!(matches!(generate(&none), Err(TecError::InvalidInput(_))));
+
+    let short = Policy {
+      length: 3,
+      ..Default::default()
+    };
+    assert!(matches!(generate(&short), Err(TecError::InvalidInput(_))));
+    // without every class required, a short password is fine
+    let short = Policy {
+      require_each: false,
+      ..short
+    };
+    assert_eq!(generate(&short).unwrap().len(), 3);
+  }
+
+  #[test]
+  fn passphrases() {
+    let pwd = passphrase(6, "-").unwrap();
+    let words: Vec<&str> = pwd.split('-').collect();
+    assert_eq!(words.len(), 6);
+    assert!(words
+      .iter()
+      .all(|word| WORDLIST.lines().any(|w| w == *word)));
+
+    assert!(matches!(passphrase(0, "-"), Err(TecError::InvalidInput(_))));
+  }
+}
@@ -1,11 +1,9 @@
-use std::{path::Path, rc::Rc, usize};
+use std::{path::Path, usize};
 
 use crate::{
-  cipher::AesCipher,
-  common::TecResult,
-  db::sqlite_conn,
+  common::{SecretString, TecResult},
   model::{Account, Pwd},
-  repo::{AccountRepo, PwdRepo},
+  repo::open_repos,
 };
 
 pub fn import_firefox_accounts<P: AsRef<Path>>(
@@ -13,12 +11,8 @@ pub fn import_firefox_accounts<P: AsRef<Path>>(
   db_path: P,
   key: &[u8],
 ) -> TecResult<()> {
-  let conn = Rc::new(sqlite_conn(db_path)?);
   let mut rdr = csv::Reader::from_path(csv_path)?;
-  let aes_cipher = Rc::new(AesCipher::from_slice(key)?);
-
-  let account_repo = AccountRepo::new(conn.clone(), aes_cipher.clone());
-  let pwd_repo = PwdRepo::new(conn, aes_cipher);
+  let (account_repo, pwd_repo, _) = open_repos(db_path, key)?;
 
   // let headers = rdr.headers()?;
   // println!("{:?}", headers);
@@ -52,7 +46,7 @@ pub fn import_firefox_accounts<P: AsRef<Path>>(
     pwd_repo.add(&Pwd {
       id: 0,
       aid,
-      password: password.to_string(),
+      password: SecretString::new(password.to_string()),
       created,
     })?;
 
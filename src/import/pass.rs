@@ -2,24 +2,17 @@ use std::{
   fs::File,
   io::{self, BufRead},
   path::Path,
-  rc::Rc,
   time::{SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
-  cipher::AesCipher,
-  common::TecResult,
-  db::sqlite_conn,
+  common::{SecretString, TecResult},
   model::{Account, Pwd},
-  repo::{AccountRepo, PwdRepo},
+  repo::{open_repos, AccountRepo, PwdRepo},
 };
 
 pub fn import_pass_accounts<P: AsRef<Path>>(src_path: P, db_path: P, key: &[u8]) -> TecResult<()> {
-  let conn = Rc::new(sqlite_conn(db_path)?);
-  let aes_cipher = Rc::new(AesCipher::from_slice(key)?);
-
-  let account_repo = AccountRepo::new(conn.clone(), aes_cipher.clone());
-  let pwd_repo = PwdRepo::new(conn, aes_cipher);
+  let (account_repo, pwd_repo, _) = open_repos(db_path, key)?;
 
   let src = File::open(src_path)?;
   let lines = io::BufReader::new(src).lines().flatten();
@@ -109,7 +102,7 @@ fn add_account(
   pwd_repo.add(&Pwd {
     id: 0,
     aid,
-    password: pwd.to_string(),
+    password: SecretString::new(pwd.to_string()),
     created: now,
   })?;
   Ok(aid)
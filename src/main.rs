@@ -1,16 +1,30 @@
-use std::{env, fs::create_dir_all, path::Path, time::Duration};
+use std::{env, fs::create_dir_all, path::Path, process, time::Duration};
 
 use argh::FromArgs;
-use common::TecResult;
-use tui::auth;
+use breach::BreachList;
+use cipher::Argon2Params;
+use cli::{Command, PasswordSource};
+use common::{TecError, TecResult};
+use db::{sqlite_conn, KeyStore};
+use repo::{open_repos, verify_manifest};
+use tui::{
+  auth, init_clipboard, AccountOptions, AuthAction, AuthOptions, ClipboardKind, ClipboardOptions,
+  LengthLimits, LockOptions,
+};
 
+mod audit;
+mod breach;
 mod cipher;
+mod cli;
 mod common;
 mod db;
+mod generator;
 mod hex;
 mod import;
+mod memory;
 mod model;
 mod repo;
+mod strength;
 mod tui;
 
 /*
@@ -40,6 +54,70 @@ struct Cli {
   /// import pass accounts
   #[argh(option)]
   import_pass: Option<String>,
+  /// change the master password
+  #[argh(switch)]
+  change_password: bool,
+  /// re-encrypt the vault with a newly generated key
+  #[argh(switch)]
+  rotate_key: bool,
+  /// re-seal the key with argon2 parameters that take about the given time in ms to unlock
+  #[argh(option)]
+  calibrate: Option<u64>,
+  /// check the vault for rows that were modified, added or deleted outside of tecpass
+  #[argh(switch)]
+  verify: bool,
+  /// sign the rows reported by --verify as genuine, once the changes are known to be yours
+  #[argh(switch)]
+  accept_changes: bool,
+  /// generate a new recovery code, replacing the current one
+  #[argh(switch)]
+  recovery_code: bool,
+  /// keyfile required in addition to the password, a random one is generated at this path
+  /// if it does not exist when the key is sealed
+  #[argh(option)]
+  keyfile: Option<String>,
+  /// require the recovery code after this many failed logins in a row
+  #[argh(option)]
+  max_attempts: Option<u32>,
+  /// length of a new master password in characters, as min-max or min-, 8- by default
+  #[argh(option, default = "LengthLimits::MASTER_PASSWORD")]
+  master_length: LengthLimits,
+  /// length of a stored password in characters, as min-max or min-, 1- by default
+  #[argh(option, default = "LengthLimits::ACCOUNT_PASSWORD")]
+  password_length: LengthLimits,
+  /// report a password unchanged for more than this many days, 0 to never report it
+  #[argh(option, default = "365")]
+  max_age: u64,
+  /// offline copy of the Have I Been Pwned sha1 or ntlm hashes to check the passwords
+  /// against, a sorted file or a directory of range files
+  #[argh(option)]
+  breach_list: Option<String>,
+  /// read the master password from the first line of this file descriptor
+  #[argh(option)]
+  password_fd: Option<i32>,
+  /// read the master password from this environment variable
+  #[argh(option)]
+  password_env: Option<String>,
+  /// lock the vault after this many seconds without a key press, 0 to never lock
+  #[argh(option, default = "300")]
+  lock_after: u64,
+  /// do not lock the vault when the terminal loses focus
+  #[argh(switch)]
+  no_focus_lock: bool,
+  /// clear a copied password from the clipboard after this many seconds, 0 to keep it
+  #[argh(option, default = "30")]
+  clear_clipboard: u64,
+  /// clipboard to copy to: auto, wayland, x11 or osc52 (through the terminal, e.g. over ssh)
+  #[argh(option, default = "ClipboardKind::Auto")]
+  clipboard: ClipboardKind,
+  /// shell command to copy with, given the content on stdin, instead of --clipboard
+  #[argh(option)]
+  copy_command: Option<String>,
+  /// shell command to paste with, printing the content on stdout, used with --copy-command
+  #[argh(option)]
+  paste_command: Option<String>,
+  #[argh(subcommand)]
+  command: Option<Command>,
 }
 
 fn parse_config_path(path: &str) -> String {
@@ -53,17 +131,134 @@ fn parse_config_path(path: &str) -> String {
 
 fn main() -> TecResult<()> {
   let cli: Cli = argh::from_env();
+  if let Err(err) = memory::disable_core_dumps() {
+    eprintln!("warning: could not disable core dumps - {err}");
+  }
   let tick_rate = Duration::from_millis(cli.tick_rate);
   let config_path = parse_config_path(&cli.config_path);
 
   // let config_path = Path::new(&cli.config_path);
   create_dir_all(&config_path)?;
 
-  let key = auth(tick_rate, &config_path)?;
+  let opts = AuthOptions {
+    keyfile: cli.keyfile.as_deref().map(|p| parse_config_path(p).into()),
+    max_attempts: cli.max_attempts,
+    master_length: cli.master_length,
+  };
+  // stdin is only read when it isn't a terminal
+  let source = PasswordSource::new(cli.password_fd, cli.password_env.clone());
+  let account_opts = AccountOptions {
+    password_length: cli.password_length,
+    max_age: match cli.max_age {
+      0 => None,
+      days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+    },
+    breaches: or_exit(
+      cli
+        .breach_list
+        .as_deref()
+        .map(|path| BreachList::open(parse_config_path(path)))
+        .transpose(),
+    ),
+  };
+  if let Some(cmd) = cli.command {
+    run_command(cmd, &config_path, &opts, &source, account_opts);
+    return Ok(());
+  }
+
+  // the other actions change an existing vault, rather than registering a new one. checked
+  // before calibrating, which would otherwise be wasted on a fresh config
+  let has_vault = KeyStore::new(Path::new(&config_path).join("tecpass.sealed.key")).has_key();
+  if !has_vault
+    && (cli.change_password || cli.rotate_key || cli.recovery_code || cli.calibrate.is_some())
+  {
+    or_exit(Err::<(), _>(TecError::NoVault(config_path.clone())));
+  }
+
+  let action = {
+    if cli.change_password {
+      AuthAction::ChangePassword
+    } else if cli.rotate_key {
+      AuthAction::RotateKey
+    } else if let Some(target) = cli.calibrate {
+      let params = calibrate(Duration::from_millis(target))?;
+      AuthAction::Reseal(params)
+    } else if cli.recovery_code {
+      AuthAction::NewRecovery
+    } else {
+      AuthAction::Login
+    }
+  };
+  let clipboard_opts = ClipboardOptions {
+    kind: cli.clipboard,
+    clear_after: match cli.clear_clipboard {
+      0 => None,
+      secs => Some(Duration::from_secs(secs)),
+    },
+    copy_command: cli.copy_command,
+    paste_command: cli.paste_command,
+  };
+  // without a terminal only a login is possible, e.g. to --verify or import from cron
+  let key = if matches!(action, AuthAction::Login) && !source.is_terminal() {
+    Some(or_exit(cli::unlock(
+      Path::new(&config_path),
+      &opts,
+      &source,
+    )))
+  } else {
+    // before the login prompt, which can already paste
+    init_clipboard(&clipboard_opts)?;
+    let key = auth(tick_rate, &config_path, action, opts.clone())?;
+    if let Some(key) = key.as_ref() {
+      memory::protect_key(key);
+    }
+    key
+  };
   if key.is_none() {
     return Ok(());
   }
 
+  if cli.change_password {
+    println!("password changed");
+    return Ok(());
+  }
+
+  if cli.rotate_key {
+    println!("vault key rotated");
+    return Ok(());
+  }
+
+  if cli.calibrate.is_some() {
+    println!("key re-sealed");
+    return Ok(());
+  }
+
+  if cli.recovery_code {
+    return Ok(());
+  }
+
+  if cli.accept_changes {
+    let db_path = Path::new(&config_path).join("tecpass.db");
+    let (_, _, manifest_repo) = open_repos(db_path, &key.unwrap())?;
+    manifest_repo.accept()?;
+    println!("changes accepted");
+    return Ok(());
+  }
+
+  if cli.verify {
+    // read only, opening the repos would bring the rows up to date
+    let conn = sqlite_conn(Path::new(&config_path).join("tecpass.db"))?;
+    let tampered = verify_manifest(&conn, &key.unwrap())?;
+    if tampered.is_empty() {
+      println!("vault intact");
+      return Ok(());
+    }
+    for t in tampered {
+      println!("{t}");
+    }
+    process::exit(cli::exit_code(&TecError::Tampered));
+  }
+
   if let Some(csv_path) = cli.import_firefox {
     let db_path = Path::new(&config_path)
       .join("tecpass.db")
@@ -88,10 +283,61 @@ fn main() -> TecResult<()> {
   // let tick_rate = Duration::from_millis(200);
   // let config_path = "./dev";
 
-  tui::run(tick_rate, config_path, key.unwrap())?;
+  let lock_opts = LockOptions {
+    after: match cli.lock_after {
+      0 => None,
+      secs => Some(Duration::from_secs(secs)),
+    },
+    on_focus_loss: !cli.no_focus_lock,
+  };
+  tui::run(
+    tick_rate,
+    config_path,
+    key.unwrap(),
+    opts,
+    lock_opts,
+    clipboard_opts,
+    account_opts,
+  )?;
   Ok(())
 }
 
+fn run_command(
+  cmd: Command,
+  config_path: &str,
+  opts: &AuthOptions,
+  source: &PasswordSource,
+  account_opts: AccountOptions,
+) {
+  or_exit(cli::run(
+    cmd,
+    Path::new(config_path),
+    opts,
+    source,
+    account_opts,
+  ));
+}
+
+// scripts get the error on stderr and a non-zero exit code
+fn or_exit<T>(res: TecResult<T>) -> T {
+  match res {
+    Ok(value) => value,
+    Err(err) => {
+      eprintln!("tecpass: {err}");
+      process::exit(cli::exit_code(&err));
+    }
+  }
+}
+
+fn calibrate(target: Duration) -> TecResult<Argon2Params> {
+  println!("calibrating argon2 for {} ms ...", target.as_millis());
+  let params = Argon2Params::calibrate(target)?;
+  let elapsed = params.measure()?;
+  println!("parameters: {params}");
+  println!("measured:   {} ms", elapsed.as_millis());
+  Ok(params)
+}
+
 // fn import_firefox(csv_path: String) -> TecResult<()> {
 //   println!("{csv_path}");
 //
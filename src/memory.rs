@@ -0,0 +1,77 @@
+use std::{io, mem};
+
+// keep the process out of core dumps, and out of reach of ptrace from the same user
+pub fn disable_core_dumps() -> io::Result<()> {
+  let limit = libc::rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+
+  #[cfg(target_os = "linux")]
+  if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+pub fn is_dumpable() -> bool {
+  #[cfg(target_os = "linux")]
+  if unsafe { libc::prctl(libc::PR_GET_DUMPABLE, 0, 0, 0, 0) } == 0 {
+    return false;
+  }
+
+  let mut limit = libc::rlimit {
+    rlim_cur: 0,
+    rlim_max: 0,
+  };
+  unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) };
+  limit.rlim_cur != 0
+}
+
+// keep the pages holding a secret from being swapped out. the pages stay locked after the
+// secret is dropped, since other secrets may share them, so only the buffers that live as
+// long as the vault is open, or a view of it, are locked.
+pub fn lock(bytes: &[u8]) -> io::Result<()> {
+  if bytes.is_empty() {
+    return Ok(());
+  }
+  if unsafe { libc::mlock(bytes.as_ptr().cast(), bytes.len()) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+// once the vault is unlocked. core dumps are disabled again in case something turned
+// them back on since startup, a failure to do so was already reported then.
+pub fn protect_key(key: &[u8]) {
+  let _ = disable_core_dumps();
+  if let Err(err) = lock(key) {
+    eprintln!("warning: could not lock memory, secrets may be swapped to disk - {err}");
+  }
+}
+
+pub fn lock_value<T>(value: &T) -> io::Result<()> {
+  let ptr = (value as *const T).cast::<u8>();
+  lock(unsafe { std::slice::from_raw_parts(ptr, mem::size_of::<T>()) })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn not_dumpable_after_disabling_core_dumps() {
+    disable_core_dumps().unwrap();
+    assert!(!is_dumpable());
+  }
+
+  #[test]
+  fn lock_secret() {
+    let secret = vec![7u8; 32];
+    lock(&secret).unwrap();
+  }
+}
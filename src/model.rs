@@ -1,6 +1,10 @@
+use serde::Serialize;
 use unicode_width::UnicodeWidthStr;
+use zeroize::Zeroize;
 
-#[derive(Debug, Default, Clone)]
+use crate::common::SecretString;
+
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct Account {
   pub id: u32,
   pub url: String,
@@ -41,16 +45,24 @@ impl Account {
   }
 }
 
+impl Drop for Account {
+  fn drop(&mut self) {
+    self.url.zeroize();
+    self.username.zeroize();
+  }
+}
+
 impl AsRef<Account> for Account {
   fn as_ref(&self) -> &Account {
     self
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Pwd {
   pub id: u32,
+  #[serde(skip)]
   pub aid: u32,
-  pub password: String,
+  pub password: SecretString,
   pub created: usize,
 }
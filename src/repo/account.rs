@@ -2,12 +2,19 @@ use std::rc::Rc;
 
 use rusqlite::Connection;
 
-use crate::{cipher::AesCipher, common::TecResult, model::Account};
+use crate::{
+  cipher::AesCipher,
+  common::{secret_string, TecError, TecResult},
+  model::Account,
+};
+
+use super::manifest::{ManifestRepo, ACCOUNT};
 
 pub struct AccountRepo {
   // conn: &'Connection,
   conn: Rc<Connection>,
   cipher: Rc<AesCipher>,
+  manifest: Rc<ManifestRepo>,
 }
 
 /*
@@ -21,21 +28,40 @@ CREATE TABLE if not exists account (
 */
 
 impl AccountRepo {
-  pub fn new(conn: Rc<Connection>, cipher: Rc<AesCipher>) -> Self {
-    Self { conn, cipher }
+  pub fn new(conn: Rc<Connection>, cipher: Rc<AesCipher>, manifest: Rc<ManifestRepo>) -> Self {
+    Self {
+      conn,
+      cipher,
+      manifest,
+    }
   }
 
   // to read
   // Is there a way to "Get or Insert" in a single query_row call? #1349
   // https://github.com/rusqlite/rusqlite/discussions/1349
   pub fn add(&self, a: &Account) -> TecResult<u32> {
-    let url = Some(self.cipher.encypt(a.url().as_bytes())?);
-    let username = Some(self.cipher.encypt(a.username().as_bytes())?);
-    let id = self.conn.query_row(
-      "INSERT INTO account (url, username, created, changed) VALUES (?1, ?2, ?3, ?4) RETURNING id",
-      (&url, &username, &a.created, &a.changed),
-      |row| row.get(0),
+    self.manifest.check()?;
+    // the id is part of the associated data, so it is allocated before encrypting
+    let tx = self.conn.unchecked_transaction()?;
+    let id: u32 = tx.query_row("SELECT coalesce(max(id), 0) + 1 FROM account", [], |row| {
+      row.get(0)
+    })?;
+    let url = Some(
+      self
+        .cipher
+        .encypt_aad(a.url().as_bytes(), &account_aad("url", id))?,
+    );
+    let username = Some(
+      self
+        .cipher
+        .encypt_aad(a.username().as_bytes(), &account_aad("username", id))?,
+    );
+    tx.execute(
+      "INSERT INTO account (id, url, username, created, changed) VALUES (?1, ?2, ?3, ?4, ?5)",
+      (&id, &url, &username, &a.created, &a.changed),
     )?;
+    self.manifest.update(ACCOUNT, id)?;
+    tx.commit()?;
     // self.conn.execute(
     //   "INSERT INTO account (url, username, created, changed) VALUES (?1, ?2, ?3, ?4)",
     //   (&a.url, &a.username, &a.created, &a.changed),
@@ -44,18 +70,33 @@ impl AccountRepo {
   }
 
   pub(crate) fn update(&self, a: &Account) -> TecResult<()> {
-    let url = Some(self.cipher.encypt(a.url().as_bytes())?);
-    let username = Some(self.cipher.encypt(a.username().as_bytes())?);
-    let mut stmt = self
-      .conn
-      .prepare("UPDATE account SET url = ?1, username = ?2, changed = ?3 WHERE id=?4")?;
+    self.manifest.check()?;
+    let url = Some(
+      self
+        .cipher
+        .encypt_aad(a.url().as_bytes(), &account_aad("url", a.id))?,
+    );
+    let username = Some(
+      self
+        .cipher
+        .encypt_aad(a.username().as_bytes(), &account_aad("username", a.id))?,
+    );
+    let tx = self.conn.unchecked_transaction()?;
+    let mut stmt =
+      tx.prepare("UPDATE account SET url = ?1, username = ?2, changed = ?3 WHERE id=?4")?;
     stmt.execute((&url, &username, &a.changed, &a.id))?;
+    drop(stmt);
+    self.manifest.update(ACCOUNT, a.id)?;
+    tx.commit()?;
     Ok(())
   }
 
   pub(crate) fn delete(&self, id: u32) -> TecResult<()> {
-    let mut stmt = self.conn.prepare("DELETE FROM account WHERE id = ?1")?;
-    stmt.execute([id])?;
+    self.manifest.check()?;
+    let tx = self.conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM account WHERE id = ?1", [id])?;
+    self.manifest.remove(ACCOUNT, id)?;
+    tx.commit()?;
     Ok(())
   }
 
@@ -63,26 +104,73 @@ impl AccountRepo {
   // Iterator
   // https://github.com/rusqlite/rusqlite/discussions/1198
   pub fn all(&self) -> TecResult<Vec<Account>> {
+    self
+      .decrypt_all()?
+      .into_iter()
+      .map(|(_, res)| res)
+      .collect()
+  }
+
+  // the accounts that decrypt, and the ids of those that don't, e.g. with a blob moved
+  // over from another row, so that the rest of a tampered vault can still be read
+  pub fn all_readable(&self) -> TecResult<(Vec<Account>, Vec<u32>)> {
+    let mut accounts = vec![];
+    let mut unreadable = vec![];
+    for (id, res) in self.decrypt_all()? {
+      match res {
+        Ok(account) => accounts.push(account),
+        Err(TecError::InvalidCiphertext(_)) => unreadable.push(id),
+        Err(err) => return Err(err),
+      }
+    }
+    Ok((accounts, unreadable))
+  }
+
+  fn decrypt_all(&self) -> TecResult<Vec<(u32, TecResult<Account>)>> {
     let mut stmt = self
       .conn
       .prepare("SELECT id, url, username, created, changed FROM account")?;
     let iter = stmt.query_map([], |row| {
       let cipher_url: Vec<u8> = row.get(1)?;
       let cipher_username: Vec<u8> = row.get(2)?;
-      let plain_url = self.cipher.decypt(&cipher_url).unwrap();
-      let plain_username = self.cipher.decypt(&cipher_username).unwrap();
-      Ok(Account {
-        id: row.get(0)?,
-        // url: row.get(1)?,
-        // username: row.get(2)?,
-        url: String::from_utf8(plain_url).unwrap(),
-        username: String::from_utf8(plain_username).unwrap(),
-        created: row.get(3)?,
-        changed: row.get(4)?,
-      })
+      Ok((
+        Account {
+          id: row.get(0)?,
+          // url: row.get(1)?,
+          // username: row.get(2)?,
+          url: "".to_string(),
+          username: "".to_string(),
+          created: row.get(3)?,
+          changed: row.get(4)?,
+        },
+        cipher_url,
+        cipher_username,
+      ))
     })?;
 
-    Ok(iter.collect::<rusqlite::Result<Vec<Account>>>()?)
+    let mut accounts = vec![];
+    for res in iter {
+      let (a, cipher_url, cipher_username) = res?;
+      accounts.push((a.id, self.decrypt(a, &cipher_url, &cipher_username)));
+    }
+    Ok(accounts)
+  }
+
+  fn decrypt(
+    &self,
+    mut a: Account,
+    cipher_url: &[u8],
+    cipher_username: &[u8],
+  ) -> TecResult<Account> {
+    let plain_url = self
+      .cipher
+      .decypt_aad(cipher_url, &account_aad("url", a.id))?;
+    let plain_username = self
+      .cipher
+      .decypt_aad(cipher_username, &account_aad("username", a.id))?;
+    a.url = secret_string(plain_url)?.to_string();
+    a.username = secret_string(plain_username)?.to_string();
+    Ok(a)
   }
 
   // pub fn query(&self, q: &str) -> TecResult<Vec<Account>> {
@@ -103,3 +191,7 @@ impl AccountRepo {
   //   Ok(iter.collect::<rusqlite::Result<Vec<Account>>>()?)
   // }
 }
+
+pub(crate) fn account_aad(column: &str, id: u32) -> String {
+  format!("account.{column}.{id}")
+}
@@ -0,0 +1,267 @@
+use std::{collections::BTreeMap, fmt, rc::Rc};
+
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, OptionalExtension};
+use sha3::Sha3_256;
+use zeroize::Zeroizing;
+
+use crate::{
+  cipher::AesCipher,
+  common::{SecretBytes, TecError, TecResult},
+  hex, memory,
+};
+
+use super::migrate;
+
+/*
+CREATE TABLE if not exists manifest (
+    tbl TEXT NOT NULL,
+    rid INTEGER NOT NULL,
+    mac BLOB NOT NULL,
+    PRIMARY KEY (tbl, rid)
+);
+
+Every account and pwd row has a MAC over its id and columns in `manifest`, and the whole
+manifest has a MAC in `meta`, both keyed from the vault key. Rows that are modified,
+added or deleted behind our back are caught by the former, entries deleted together
+with their rows by the latter. Nothing is written to a tampered vault, as signing it would
+pass the tampering off as genuine, until the changes are accepted.
+
+That a vault has a manifest is sealed along with its key, as `user_version` can be set
+back by anyone who can write to the database. A vault is only signed for the first time
+when it is unlocked by a key sealed before it had a manifest, every vault opened since is
+expected to have one.
+*/
+
+type HmacSha3 = Hmac<Sha3_256>;
+
+pub const ACCOUNT: &str = "account";
+pub const PWD: &str = "pwd";
+const TABLES: [&str; 2] = [ACCOUNT, PWD];
+const MANIFEST_MAC: &str = "manifest_mac";
+// user_version 2: the manifest is maintained
+const VERSION_MANIFEST: u32 = 2;
+
+pub enum Tamper {
+  Modified(&'static str, u32),
+  Inserted(&'static str, u32),
+  Deleted(&'static str, u32),
+  Manifest,
+  // the key was sealed since the vault has a manifest, but the database no longer has one
+  Missing,
+}
+
+impl fmt::Display for Tamper {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Tamper::Modified(tbl, rid) => write!(f, "{tbl} {rid}: modified"),
+      Tamper::Inserted(tbl, rid) => write!(f, "{tbl} {rid}: inserted"),
+      Tamper::Deleted(tbl, rid) => write!(f, "{tbl} {rid}: deleted"),
+      Tamper::Manifest => write!(
+        f,
+        "manifest: signature mismatch, rows may have been deleted"
+      ),
+      Tamper::Missing => write!(f, "manifest: missing from a vault that had one"),
+    }
+  }
+}
+
+pub struct ManifestRepo {
+  conn: Rc<Connection>,
+  mac_key: SecretBytes,
+}
+
+impl ManifestRepo {
+  pub fn new(conn: Rc<Connection>, key: &[u8]) -> TecResult<Self> {
+    let mac_key = mac_key(key)?;
+    let _ = memory::lock(&mac_key);
+    Ok(Self { conn, mac_key })
+  }
+
+  // called before a row is written
+  pub fn check(&self) -> TecResult<()> {
+    if !self.verify()?.is_empty() {
+      return Err(TecError::Tampered);
+    }
+    Ok(())
+  }
+
+  // sign the current state of the vault as genuine, once the changes are known to be ours
+  pub fn accept(&self) -> TecResult<()> {
+    let tx = self.conn.unchecked_transaction()?;
+    rebuild(&tx, &self.mac_key)?;
+    tx.commit()?;
+    Ok(())
+  }
+
+  // record the current state of a row, called in the transaction that wrote it
+  pub fn update(&self, tbl: &'static str, rid: u32) -> TecResult<()> {
+    if let Some((_, mac)) = row_macs(&self.conn, &self.mac_key, tbl, Some(rid))?.pop() {
+      self.conn.execute(
+        "INSERT OR REPLACE INTO manifest (tbl, rid, mac) VALUES (?1, ?2, ?3)",
+        (tbl, rid, mac),
+      )?;
+    }
+    sign(&self.conn, &self.mac_key)
+  }
+
+  pub fn remove(&self, tbl: &'static str, rid: u32) -> TecResult<()> {
+    self.conn.execute(
+      "DELETE FROM manifest WHERE tbl = ?1 AND rid = ?2",
+      (tbl, rid),
+    )?;
+    sign(&self.conn, &self.mac_key)
+  }
+
+  pub fn remove_pwds(&self, aid: u32) -> TecResult<()> {
+    self.conn.execute(
+      "DELETE FROM manifest WHERE tbl = ?1 AND rid IN (SELECT id FROM pwd WHERE aid = ?2)",
+      (PWD, aid),
+    )?;
+    sign(&self.conn, &self.mac_key)
+  }
+
+  pub fn verify(&self) -> TecResult<Vec<Tamper>> {
+    verify(&self.conn, &self.mac_key)
+  }
+}
+
+// start tracking a vault that has no manifest yet, once its rows are bound to their place
+pub fn init_manifest(conn: &Connection, key: &[u8]) -> TecResult<()> {
+  migrate(conn, &AesCipher::from_slice(key)?)?;
+  if version(conn)? < VERSION_MANIFEST {
+    let tx = conn.unchecked_transaction()?;
+    rebuild(&tx, &mac_key(key)?)?;
+    tx.commit()?;
+  }
+  Ok(())
+}
+
+pub fn verify_manifest(conn: &Connection, key: &[u8]) -> TecResult<Vec<Tamper>> {
+  verify(conn, &mac_key(key)?)
+}
+
+// re-sign every row, e.g. after the vault key is rotated, the caller owns the transaction
+pub fn rebuild_manifest(conn: &Connection, key: &[u8]) -> TecResult<()> {
+  rebuild(conn, &mac_key(key)?)
+}
+
+fn mac_key(key: &[u8]) -> TecResult<SecretBytes> {
+  let mut mac = <HmacSha3 as Mac>::new_from_slice(key)?;
+  mac.update(b"tecpass-manifest");
+  Ok(Zeroizing::new(mac.finalize().into_bytes().to_vec()))
+}
+
+fn version(conn: &Connection) -> TecResult<u32> {
+  Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+fn rebuild(conn: &Connection, mac_key: &[u8]) -> TecResult<()> {
+  conn.execute("DELETE FROM manifest", ())?;
+  let mut stmt = conn.prepare("INSERT INTO manifest (tbl, rid, mac) VALUES (?1, ?2, ?3)")?;
+  for tbl in TABLES {
+    for (rid, mac) in row_macs(conn, mac_key, tbl, None)? {
+      stmt.execute((tbl, rid, mac))?;
+    }
+  }
+  sign(conn, mac_key)?;
+  conn.pragma_update(None, "user_version", VERSION_MANIFEST)?;
+  Ok(())
+}
+
+fn verify(conn: &Connection, mac_key: &[u8]) -> TecResult<Vec<Tamper>> {
+  if version(conn)? < VERSION_MANIFEST {
+    return Ok(vec![Tamper::Missing]);
+  }
+
+  let mut tampered = vec![];
+  let signature: Option<String> = conn
+    .query_row(
+      "SELECT value FROM meta WHERE name = ?1",
+      [MANIFEST_MAC],
+      |row| row.get(0),
+    )
+    .optional()?;
+  if signature.as_deref() != Some(manifest_mac(conn, mac_key)?.as_str()) {
+    tampered.push(Tamper::Manifest);
+  }
+
+  for tbl in TABLES {
+    let mut entries = {
+      let mut stmt = conn.prepare("SELECT rid, mac FROM manifest WHERE tbl = ?1")?;
+      let iter = stmt.query_map([tbl], |row| Ok((row.get::<_, u32>(0)?, row.get(1)?)))?;
+      iter.collect::<rusqlite::Result<BTreeMap<u32, Vec<u8>>>>()?
+    };
+    for (rid, mac) in row_macs(conn, mac_key, tbl, None)? {
+      match entries.remove(&rid) {
+        Some(expected) if expected == mac => {}
+        Some(_) => tampered.push(Tamper::Modified(tbl, rid)),
+        None => tampered.push(Tamper::Inserted(tbl, rid)),
+      }
+    }
+    for rid in entries.into_keys() {
+      tampered.push(Tamper::Deleted(tbl, rid));
+    }
+  }
+  Ok(tampered)
+}
+
+fn sign(conn: &Connection, mac_key: &[u8]) -> TecResult<()> {
+  conn.execute(
+    "INSERT OR REPLACE INTO meta (name, value) VALUES (?1, ?2)",
+    (MANIFEST_MAC, manifest_mac(conn, mac_key)?),
+  )?;
+  Ok(())
+}
+
+fn manifest_mac(conn: &Connection, mac_key: &[u8]) -> TecResult<String> {
+  let mut mac = <HmacSha3 as Mac>::new_from_slice(mac_key)?;
+  let mut stmt = conn.prepare("SELECT tbl, rid, mac FROM manifest ORDER BY tbl, rid")?;
+  let mut rows = stmt.query([])?;
+  while let Some(row) = rows.next()? {
+    let tbl: String = row.get(0)?;
+    let rid: u32 = row.get(1)?;
+    let row_mac: Vec<u8> = row.get(2)?;
+    update_field(&mut mac, tbl.as_bytes());
+    update_field(&mut mac, &rid.to_le_bytes());
+    update_field(&mut mac, &row_mac);
+  }
+  hex::encode(&mac.finalize().into_bytes())
+}
+
+fn row_macs(
+  conn: &Connection,
+  mac_key: &[u8],
+  tbl: &str,
+  rid: Option<u32>,
+) -> TecResult<Vec<(u32, Vec<u8>)>> {
+  let sql = match tbl {
+    ACCOUNT => "SELECT id, url, username, created, changed FROM account",
+    _ => "SELECT id, aid, password, created FROM pwd",
+  };
+  let mut stmt = conn.prepare(&format!("{sql} WHERE ?1 IS NULL OR id = ?1"))?;
+  let mut rows = stmt.query([rid])?;
+
+  let mut macs = vec![];
+  while let Some(row) = rows.next()? {
+    let id: u32 = row.get(0)?;
+    let mut mac = <HmacSha3 as Mac>::new_from_slice(mac_key)?;
+    update_field(&mut mac, tbl.as_bytes());
+    for i in 0..row.as_ref().column_count() {
+      let field = match row.get_ref(i)? {
+        rusqlite::types::ValueRef::Integer(n) => n.to_le_bytes().to_vec(),
+        rusqlite::types::ValueRef::Blob(b) => b.to_vec(),
+        _ => vec![],
+      };
+      update_field(&mut mac, &field);
+    }
+    macs.push((id, mac.finalize().into_bytes().to_vec()));
+  }
+  Ok(macs)
+}
+
+// length prefixed, so that fields can't be shifted into each other
+fn update_field(mac: &mut HmacSha3, field: &[u8]) {
+  mac.update(&(field.len() as u64).to_le_bytes());
+  mac.update(field);
+}
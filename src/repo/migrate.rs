@@ -0,0 +1,71 @@
+use rusqlite::{Connection, Transaction};
+
+use crate::{cipher::AesCipher, common::TecResult};
+
+use super::{account::account_aad, pwd::pwd_aad};
+
+// user_version 1: every blob is bound to its table, column and row
+const VERSION_AAD: u32 = 1;
+
+// bind the blobs written before the associated data was introduced to their rows
+pub fn migrate(conn: &Connection, cipher: &AesCipher) -> TecResult<()> {
+  let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+  if version >= VERSION_AAD {
+    return Ok(());
+  }
+
+  let tx = conn.unchecked_transaction()?;
+  rewrite(&tx, |blob, aad| {
+    cipher.encypt_aad(&cipher.decypt(blob)?, aad)
+  })?;
+  tx.pragma_update(None, "user_version", VERSION_AAD)?;
+  tx.commit()?;
+  Ok(())
+}
+
+// re-encrypt every blob under a new key, the caller owns the transaction
+pub fn reencrypt(tx: &Transaction, old: &AesCipher, new: &AesCipher) -> TecResult<()> {
+  rewrite(tx, |blob, aad| {
+    new.encypt_aad(&old.decypt_aad(blob, aad)?, aad)
+  })
+}
+
+fn rewrite(tx: &Transaction, f: impl Fn(&[u8], &str) -> TecResult<Vec<u8>>) -> TecResult<()> {
+  let accounts = {
+    let mut stmt = tx.prepare("SELECT id, url, username FROM account")?;
+    let iter = stmt.query_map([], |row| {
+      Ok((
+        row.get::<_, u32>(0)?,
+        row.get::<_, Vec<u8>>(1)?,
+        row.get::<_, Vec<u8>>(2)?,
+      ))
+    })?;
+    iter.collect::<rusqlite::Result<Vec<_>>>()?
+  };
+
+  let mut stmt = tx.prepare("UPDATE account SET url = ?1, username = ?2 WHERE id = ?3")?;
+  for (id, url, username) in accounts {
+    let url = f(&url, &account_aad("url", id))?;
+    let username = f(&username, &account_aad("username", id))?;
+    stmt.execute((&url, &username, &id))?;
+  }
+
+  let pwds = {
+    let mut stmt = tx.prepare("SELECT id, aid, password FROM pwd")?;
+    let iter = stmt.query_map([], |row| {
+      Ok((
+        row.get::<_, u32>(0)?,
+        row.get::<_, u32>(1)?,
+        row.get::<_, Vec<u8>>(2)?,
+      ))
+    })?;
+    iter.collect::<rusqlite::Result<Vec<_>>>()?
+  };
+
+  let mut stmt = tx.prepare("UPDATE pwd SET password = ?1 WHERE id = ?2")?;
+  for (id, aid, password) in pwds {
+    let password = f(&password, &pwd_aad(id, aid))?;
+    stmt.execute((&password, &id))?;
+  }
+  Ok(())
+}
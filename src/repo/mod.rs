@@ -1,5 +1,28 @@
 mod account;
+mod manifest;
+mod migrate;
 mod pwd;
 
+use std::{path::Path, rc::Rc};
+
 pub use account::AccountRepo;
+pub use manifest::{init_manifest, rebuild_manifest, verify_manifest, ManifestRepo};
+pub use migrate::{migrate, reencrypt};
 pub use pwd::PwdRepo;
+
+use crate::{cipher::AesCipher, common::TecResult, db::sqlite_conn, memory};
+
+pub fn open_repos(
+  db_path: impl AsRef<Path>,
+  key: &[u8],
+) -> TecResult<(AccountRepo, PwdRepo, Rc<ManifestRepo>)> {
+  let conn = Rc::new(sqlite_conn(db_path)?);
+  let cipher = Rc::new(AesCipher::from_slice(key)?);
+  let _ = memory::lock_value(&*cipher);
+  migrate(&conn, &cipher)?;
+  let manifest = Rc::new(ManifestRepo::new(conn.clone(), key)?);
+
+  let account_repo = AccountRepo::new(conn.clone(), cipher.clone(), manifest.clone());
+  let pwd_repo = PwdRepo::new(conn, cipher, manifest.clone());
+  Ok((account_repo, pwd_repo, manifest))
+}
@@ -2,7 +2,13 @@ use std::rc::Rc;
 
 use rusqlite::Connection;
 
-use crate::{cipher::AesCipher, common::TecResult, model::Pwd};
+use crate::{
+  cipher::AesCipher,
+  common::{secret_string, SecretString, TecResult},
+  model::Pwd,
+};
+
+use super::manifest::{ManifestRepo, PWD};
 
 /*
 CREATE TABLE if not exists pwd (
@@ -17,20 +23,36 @@ pub struct PwdRepo {
   // conn: &'a Connection,
   conn: Rc<Connection>,
   cipher: Rc<AesCipher>,
+  manifest: Rc<ManifestRepo>,
 }
 
 impl PwdRepo {
-  pub fn new(conn: Rc<Connection>, cipher: Rc<AesCipher>) -> Self {
-    Self { conn, cipher }
+  pub fn new(conn: Rc<Connection>, cipher: Rc<AesCipher>, manifest: Rc<ManifestRepo>) -> Self {
+    Self {
+      conn,
+      cipher,
+      manifest,
+    }
   }
 
   pub fn add(&self, pwd: &Pwd) -> TecResult<u32> {
-    let password = Some(self.cipher.encypt(pwd.password.as_bytes())?);
-    let id: u32 = self.conn.query_row(
-      "INSERT INTO pwd (aid, password, created) VALUES (?1, ?2, ?3) RETURNING id",
-      (&pwd.aid, &password, &pwd.created),
-      |row| row.get(0),
+    self.manifest.check()?;
+    // the id is part of the associated data, so it is allocated before encrypting
+    let tx = self.conn.unchecked_transaction()?;
+    let id: u32 = tx.query_row("SELECT coalesce(max(id), 0) + 1 FROM pwd", [], |row| {
+      row.get(0)
+    })?;
+    let password = Some(
+      self
+        .cipher
+        .encypt_aad(pwd.password.as_bytes(), &pwd_aad(id, pwd.aid))?,
+    );
+    tx.execute(
+      "INSERT INTO pwd (id, aid, password, created) VALUES (?1, ?2, ?3, ?4)",
+      (&id, &pwd.aid, &password, &pwd.created),
     )?;
+    self.manifest.update(PWD, id)?;
+    tx.commit()?;
     Ok(id)
   }
 
@@ -41,24 +63,41 @@ impl PwdRepo {
     // let rows = stmt.query_map(&[(":id", &"one")], |row| row.get(0))?;
     let iter = stmt.query_map(&[(":aid", &aid)], |row| {
       let cipher_pwd: Vec<u8> = row.get(2)?;
-      let plain_pwd = self.cipher.decypt(&cipher_pwd).unwrap();
-      Ok(Pwd {
-        id: row.get(0)?,
-        aid: row.get(1)?,
-        password: String::from_utf8(plain_pwd).unwrap(),
-        created: row.get(3)?,
-      })
+      Ok((
+        Pwd {
+          id: row.get(0)?,
+          aid: row.get(1)?,
+          password: SecretString::default(),
+          created: row.get(3)?,
+        },
+        cipher_pwd,
+      ))
     })?;
 
-    let mut pwds = iter.collect::<rusqlite::Result<Vec<Pwd>>>()?;
+    let mut pwds = vec![];
+    for res in iter {
+      let (mut pwd, cipher_pwd) = res?;
+      let plain_pwd = self
+        .cipher
+        .decypt_aad(&cipher_pwd, &pwd_aad(pwd.id, pwd.aid))?;
+      pwd.password = secret_string(plain_pwd)?;
+      pwds.push(pwd);
+    }
     pwds.sort_by(|a, b| b.created.partial_cmp(&a.created).unwrap());
     Ok(pwds)
     // Ok(iter.collect::<rusqlite::Result<Vec<Account>>>()?)
   }
 
   pub(crate) fn delete(&self, id: u32) -> TecResult<()> {
-    let mut stmt = self.conn.prepare("DELETE FROM pwd WHERE aid = ?1")?;
-    stmt.execute([id])?;
+    self.manifest.check()?;
+    let tx = self.conn.unchecked_transaction()?;
+    self.manifest.remove_pwds(id)?;
+    tx.execute("DELETE FROM pwd WHERE aid = ?1", [id])?;
+    tx.commit()?;
     Ok(())
   }
 }
+
+pub(crate) fn pwd_aad(id: u32, aid: u32) -> String {
+  format!("pwd.password.{id}.{aid}")
+}
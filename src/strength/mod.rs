@@ -0,0 +1,547 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use time::OffsetDateTime;
+
+use crate::generator::WORDLIST;
+
+// ranked, the most common first
+const COMMON: &str = include_str!("common.txt");
+
+const KEYBOARD: [&str; 4] = ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"];
+const KEYBOARD_SHIFTED: [&str; 4] = ["!@#$%^&*()_+", "QWERTYUIOP{}", "ASDFGHJKL:\"", "ZXCVBNM<>?"];
+// keys a walk can start on, and the neighbours it can turn to
+const KEYS: f64 = 47.0;
+const KEY_DEGREE: f64 = 4.0;
+const DATE_SEPARATORS: &str = "/-._ ";
+// a year close to now is about as likely as any of the last 20
+const MIN_YEAR_SPACE: i32 = 20;
+const MIN_MATCH_LEN: usize = 3;
+const MAX_WORD_LEN: usize = 16;
+
+/*
+A zxcvbn-style estimate: the password is split into the patterns an attacker would try
+first, common passwords, dictionary words (also in l33t and mixed case), keyboard walks,
+repeats, sequences and dates, and the rest is guessed by brute force. The split taking
+the fewest guesses wins.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+  Common,
+  Word,
+  Keyboard,
+  Repeat,
+  Sequence,
+  Date,
+}
+
+impl Pattern {
+  pub fn warning(&self) -> &'static str {
+    match self {
+      Self::Common => "a very common password",
+      Self::Word => "a dictionary word",
+      Self::Keyboard => "a keyboard pattern",
+      Self::Repeat => "repeated characters",
+      Self::Sequence => "a sequence like abc or 123",
+      Self::Date => "a date or a year",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+  // log2 of the number of guesses
+  pub bits: f64,
+  // from 0 to 4, as zxcvbn
+  pub score: u8,
+  // the pattern covering most of the password
+  pub pattern: Option<Pattern>,
+}
+
+impl Estimate {
+  pub fn label(&self) -> &'static str {
+    ["very weak", "weak", "fair", "strong", "very strong"][self.score as usize]
+  }
+
+  pub fn is_weak(&self) -> bool {
+    self.score < 3
+  }
+}
+
+struct Match {
+  start: usize,
+  end: usize,
+  bits: f64,
+  pattern: Pattern,
+}
+
+pub fn estimate(pwd: &str) -> Estimate {
+  let chars: Vec<char> = pwd.chars().collect();
+  let n = chars.len();
+
+  let mut matches = vec![];
+  dictionary_matches(&chars, &mut matches);
+  keyboard_matches(&chars, &mut matches);
+  sequence_matches(&chars, &mut matches);
+  repeat_matches(&chars, &mut matches);
+  date_matches(&chars, &mut matches);
+
+  // the fewest bits to guess the first i characters, and the match ending there
+  let random_bits = cardinality(&chars).log2();
+  let mut best: Vec<(f64, Option<usize>)> = vec![(0.0, None); n + 1];
+  for i in 1..=n {
+    best[i] = (best[i - 1].0 + random_bits, None);
+    for (k, m) in matches.iter().enumerate().filter(|(_, m)| m.end == i) {
+      let bits = best[m.start].0 + m.bits;
+      if bits < best[i].0 {
+        best[i] = (bits, Some(k));
+      }
+    }
+  }
+
+  let mut pattern = None;
+  let mut covered = 0;
+  let mut i = n;
+  while i > 0 {
+    match best[i].1 {
+      Some(k) => {
+        let m = &matches[k];
+        if m.end - m.start > covered {
+          covered = m.end - m.start;
+          pattern = Some(m.pattern);
+        }
+        i = m.start;
+      }
+      None => i -= 1,
+    }
+  }
+
+  let bits = best[n].0;
+  Estimate {
+    bits,
+    score: score(bits),
+    pattern,
+  }
+}
+
+// zxcvbn's thresholds of 10^3, 10^6, 10^8 and 10^10 guesses
+fn score(bits: f64) -> u8 {
+  let guesses = bits.exp2();
+  if guesses < 1e3 {
+    0
+  } else if guesses < 1e6 {
+    1
+  } else if guesses < 1e8 {
+    2
+  } else if guesses < 1e10 {
+    3
+  } else {
+    4
+  }
+}
+
+fn cardinality(chars: &[char]) -> f64 {
+  let mut cardinality = 0.0;
+  if chars.iter().any(|c| c.is_ascii_lowercase()) {
+    cardinality += 26.0;
+  }
+  if chars.iter().any(|c| c.is_ascii_uppercase()) {
+    cardinality += 26.0;
+  }
+  if chars.iter().any(|c| c.is_ascii_digit()) {
+    cardinality += 10.0;
+  }
+  if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+    cardinality += 33.0;
+  }
+  if chars.iter().any(|c| !c.is_ascii()) {
+    cardinality += 100.0;
+  }
+  f64::max(cardinality, 1.0)
+}
+
+// word -> (pattern, guesses)
+fn dictionary() -> &'static HashMap<&'static str, (Pattern, f64)> {
+  static DICTIONARY: OnceLock<HashMap<&'static str, (Pattern, f64)>> = OnceLock::new();
+  DICTIONARY.get_or_init(|| {
+    let words: Vec<&str> = WORDLIST.lines().collect();
+    let mut dictionary = HashMap::new();
+    for word in words.iter().filter(|w| w.len() > MIN_MATCH_LEN) {
+      dictionary.insert(*word, (Pattern::Word, words.len() as f64));
+    }
+    for (rank, pwd) in COMMON.lines().enumerate() {
+      dictionary.insert(pwd, (Pattern::Common, (rank + 1) as f64));
+    }
+    dictionary
+  })
+}
+
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let dictionary = dictionary();
+  for start in 0..chars.len() {
+    for end in (start + MIN_MATCH_LEN)..=chars.len().min(start + MAX_WORD_LEN) {
+      let token = &chars[start..end];
+      for (word, subs) in variants(token) {
+        if let Some((pattern, guesses)) = dictionary.get(word.as_str()) {
+          matches.push(Match {
+            start,
+            end,
+            bits: guesses.log2() + case_bits(token) + subs as f64,
+            pattern: *pattern,
+          });
+        }
+      }
+    }
+  }
+}
+
+// the token in lowercase, as is and with its l33t substitutions undone
+fn variants(token: &[char]) -> Vec<(String, usize)> {
+  let lower = token.iter().map(|c| c.to_ascii_lowercase()).collect();
+  let mut variants = vec![(lower, 0)];
+  for one in ['i', 'l'] {
+    let mut subs = 0;
+    let word = token
+      .iter()
+      .map(|c| {
+        let plain = match c {
+          '4' | '@' => 'a',
+          '3' => 'e',
+          '1' | '!' => one,
+          '0' => 'o',
+          '$' | '5' => 's',
+          '7' | '+' => 't',
+          c => return c.to_ascii_lowercase(),
+        };
+        subs += 1;
+        plain
+      })
+      .collect();
+    if subs > 0 {
+      variants.push((word, subs));
+    }
+  }
+  variants
+}
+
+// capitalized or all caps are tried first, any other mix of cases is a guess per letter
+fn case_bits(token: &[char]) -> f64 {
+  let upper = token.iter().filter(|c| c.is_ascii_uppercase()).count();
+  let lower = token.iter().filter(|c| c.is_ascii_lowercase()).count();
+  if upper == 0 {
+    return 0.0;
+  }
+  if lower == 0 || (upper == 1 && token[0].is_ascii_uppercase()) {
+    return 1.0;
+  }
+  let variations: f64 = (1..=upper.min(lower))
+    .map(|k| binomial(upper + lower, k))
+    .sum();
+  variations.log2()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+  (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+// row and column on a qwerty keyboard, and whether shift is held
+fn key(c: char) -> Option<(i32, i32, bool)> {
+  for (row, (keys, shifted)) in KEYBOARD.iter().zip(KEYBOARD_SHIFTED).enumerate() {
+    if let Some(col) = keys.chars().position(|k| k == c) {
+      return Some((row as i32, col as i32, false));
+    }
+    if let Some(col) = shifted.chars().position(|k| k == c) {
+      return Some((row as i32, col as i32, true));
+    }
+  }
+  None
+}
+
+// the direction from a key to a neighbour, rows are shifted half a key to the right
+fn direction(a: char, b: char) -> Option<(i32, i32)> {
+  let ((row_a, col_a, _), (row_b, col_b, _)) = (key(a)?, key(b)?);
+  let step = (row_b - row_a, col_b - col_a);
+  [(0, 1), (0, -1), (-1, 0), (-1, 1), (1, -1), (1, 0)]
+    .contains(&step)
+    .then_some(step)
+}
+
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let mut start = 0;
+  while start < chars.len() {
+    let mut end = start + 1;
+    let mut turns = 1;
+    let mut last = None;
+    while end < chars.len() {
+      let Some(step) = direction(chars[end - 1], chars[end]) else {
+        break;
+      };
+      if last.is_some_and(|last| last != step) {
+        turns += 1;
+      }
+      last = Some(step);
+      end += 1;
+    }
+    let len = end - start;
+    if len >= MIN_MATCH_LEN {
+      let shifted = chars[start..end]
+        .iter()
+        .any(|c| key(*c).is_some_and(|(_, _, shifted)| shifted));
+      let guesses = KEYS * len as f64 * KEY_DEGREE.powi(turns - 1);
+      matches.push(Match {
+        start,
+        end,
+        bits: guesses.log2() + if shifted { 1.0 } else { 0.0 },
+        pattern: Pattern::Keyboard,
+      });
+    }
+    start = end;
+  }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+  let class = |c: char| {
+    (
+      c.is_ascii_lowercase(),
+      c.is_ascii_uppercase(),
+      c.is_ascii_digit(),
+    )
+  };
+  let mut start = 0;
+  while start < chars.len() {
+    let mut end = start + 1;
+    let mut delta = 0;
+    while end < chars.len() {
+      let (a, b) = (chars[end - 1], chars[end]);
+      let step = b as i32 - a as i32;
+      if class(a) != class(b) || class(a) == (false, false, false) || step.abs() != 1 {
+        break;
+      }
+      if delta != 0 && step != delta {
+        break;
+      }
+      delta = step;
+      end += 1;
+    }
+    let len = end - start;
+    if len >= MIN_MATCH_LEN {
+      let first = chars[start];
+      let mut guesses = if "aAzZ019".contains(first) {
+        4.0
+      } else if first.is_ascii_digit() {
+        10.0
+      } else {
+        26.0
+      };
+      if delta < 0 {
+        guesses *= 2.0;
+      }
+      matches.push(Match {
+        start,
+        end,
+        bits: (guesses * len as f64).log2(),
+        pattern: Pattern::Sequence,
+      });
+      start = end - 1;
+    } else {
+      start += 1;
+    }
+  }
+}
+
+// a block repeated at least twice, e.g. `aaa` or `abcabc`
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+  for start in 0..chars.len() {
+    // longer blocks are left to brute force, which keeps long passwords fast
+    for len in 1..=((chars.len() - start) / 2).min(MAX_WORD_LEN) {
+      let base = &chars[start..start + len];
+      let mut count = 1;
+      while chars[start + count * len..].starts_with(base) {
+        count += 1;
+      }
+      if count >= 2 && count * len >= MIN_MATCH_LEN {
+        let base: String = base.iter().collect();
+        matches.push(Match {
+          start,
+          end: start + count * len,
+          bits: estimate(&base).bits + (count as f64).log2(),
+          pattern: Pattern::Repeat,
+        });
+      }
+    }
+  }
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+  for start in 0..chars.len() {
+    for end in (start + 4)..=chars.len().min(start + 10) {
+      if let Some(guesses) = date_guesses(&chars[start..end]) {
+        matches.push(Match {
+          start,
+          end,
+          bits: guesses.log2(),
+          pattern: Pattern::Date,
+        });
+      }
+    }
+  }
+}
+
+fn date_guesses(token: &[char]) -> Option<f64> {
+  let seps: Vec<char> = token
+    .iter()
+    .copied()
+    .filter(|c| !c.is_ascii_digit())
+    .collect();
+  let digits: String = token.iter().filter(|c| c.is_ascii_digit()).collect();
+
+  if seps.is_empty() && token.len() == 4 {
+    let year = digits.parse().ok()?;
+    return (1900..=2099).contains(&year).then(|| year_space(year));
+  }
+
+  let groups: Vec<String> = if seps.is_empty() {
+    vec![]
+  } else {
+    // two of the same separator, e.g. 31/12/1990
+    if seps.len() != 2 || seps[0] != seps[1] || !DATE_SEPARATORS.contains(seps[0]) {
+      return None;
+    }
+    let token: String = token.iter().collect();
+    token.split(seps[0]).map(str::to_owned).collect()
+  };
+  if !(4..=8).contains(&digits.len()) || groups.iter().any(|g| g.is_empty() || g.len() > 4) {
+    return None;
+  }
+
+  let year = splits(&digits, &groups).into_iter().find_map(|(y, m, d)| {
+    let (y, m, d): (i32, u32, u32) = (y.parse().ok()?, m.parse().ok()?, d.parse().ok()?);
+    let year = match y {
+      0..=49 => 2000 + y,
+      50..=99 => 1900 + y,
+      1900..=2099 => y,
+      _ => return None,
+    };
+    ((1..=12).contains(&m) && (1..=31).contains(&d)).then_some(year)
+  })?;
+  let guesses = year_space(year) * 365.0;
+  Some(if seps.is_empty() {
+    guesses
+  } else {
+    guesses * 4.0
+  })
+}
+
+// the (year, month, day) readings of a date, years first or last
+fn splits<'a>(digits: &'a str, groups: &'a [String]) -> Vec<(&'a str, &'a str, &'a str)> {
+  let mut parts = vec![];
+  if groups.is_empty() {
+    for a in 1..digits.len() {
+      for b in (a + 1)..digits.len() {
+        parts.push((&digits[..a], &digits[a..b], &digits[b..]));
+      }
+    }
+  } else {
+    parts.push((&groups[0][..], &groups[1][..], &groups[2][..]));
+  }
+
+  let mut readings = vec![];
+  for (first, second, third) in parts {
+    let short = |s: &str| (1..=2).contains(&s.len());
+    let year = |s: &str| s.len() == 2 || s.len() == 4;
+    if year(first) && short(second) && short(third) {
+      readings.push((first, second, third));
+    }
+    if short(first) && short(second) && year(third) {
+      readings.push((third, first, second));
+      readings.push((third, second, first));
+    }
+  }
+  readings
+}
+
+fn year_space(year: i32) -> f64 {
+  let now = OffsetDateTime::now_utc().year();
+  (year - now).abs().max(MIN_YEAR_SPACE) as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn common_passwords() {
+    for pwd in ["password", "123456", "qwerty", "letmein"] {
+      let estimate = estimate(pwd);
+      assert_eq!(estimate.score, 0, "{pwd}");
+      assert_eq!(estimate.pattern, Some(Pattern::Common), "{pwd}");
+    }
+  }
+
+  #[test]
+  fn l33t_and_case() {
+    for pwd in ["P@ssw0rd", "PASSWORD", "Sunsh1ne"] {
+      assert!(estimate(pwd).score <= 1, "{pwd}");
+    }
+  }
+
+  #[test]
+  fn dictionary_words() {
+    let estimate = estimate("elephant");
+    assert_eq!(estimate.pattern, Some(Pattern::Word));
+    assert!(estimate.is_weak());
+  }
+
+  #[test]
+  fn keyboard_walks() {
+    for pwd in ["zxcvbnm,./", "3edc4rfv5tgb", "poiuytrewq"] {
+      let estimate = estimate(pwd);
+      assert!(estimate.is_weak(), "{pwd}");
+      assert_eq!(estimate.pattern, Some(Pattern::Keyboard), "{pwd}");
+    }
+  }
+
+  #[test]
+  fn repeats() {
+    for pwd in ["aaaaaaaaaaaa", "abcabcabcabc", "xy7!xy7!xy7!"] {
+      let estimate = estimate(pwd);
+      assert!(estimate.is_weak(), "{pwd}");
+      assert_eq!(estimate.pattern, Some(Pattern::Repeat), "{pwd}");
+    }
+  }
+
+  #[test]
+  fn sequences() {
+    for pwd in ["abcdefghij", "9876543210", "MNOPQRST"] {
+      let estimate = estimate(pwd);
+      assert!(estimate.score <= 1, "{pwd}");
+      assert_eq!(estimate.pattern, Some(Pattern::Sequence), "{pwd}");
+    }
+  }
+
+  #[test]
+  fn dates() {
+    for pwd in ["19901231", "31/12/1990", "12-31-90", "1987"] {
+      let estimate = estimate(pwd);
+      assert!(estimate.is_weak(), "{pwd}");
+      assert_eq!(estimate.pattern, Some(Pattern::Date), "{pwd}");
+    }
+  }
+
+  #[test]
+  fn strong_passwords() {
+    for pwd in [
+      "q{>6!qnSEFS/c:n<ja1d",
+      "video-travel-rate-cart-swift-vast",
+      "Kp7#vR2m!xQ9",
+    ] {
+      assert_eq!(estimate(pwd).score, 4, "{pwd}");
+    }
+  }
+
+  #[test]
+  fn empty() {
+    let estimate = estimate("");
+    assert_eq!(estimate.score, 0);
+    assert_eq!(estimate.pattern, None);
+  }
+}
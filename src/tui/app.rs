@@ -1,25 +1,32 @@
-use std::{path::Path, rc::Rc};
+use std::{
+  path::{Path, PathBuf},
+  time::{Duration, Instant},
+};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
   layout::{Constraint, Direction, Layout, Rect},
+  style::{Style, Stylize},
   text::Line,
+  widgets::{Block, Borders},
   Frame,
 };
 
 use crate::{
-  cipher::AesCipher,
-  common::TecResult,
-  db::sqlite_conn,
+  audit::audit,
+  breach::{breached, BreachList},
+  common::{SecretString, TecError, TecResult},
   model::{Account, Pwd},
-  repo::{AccountRepo, PwdRepo},
+  repo::{open_repos, AccountRepo, PwdRepo},
 };
 
 use super::{
+  auth::{draw_auth, Auth, AuthAction, AuthOptions},
   module::{
-    draw_account_table, draw_confirm, draw_form, draw_view, AccountTable, Confirm, Form, View,
+    draw_account_table, draw_confirm, draw_form, draw_report, draw_view, AccountTable, Confirm,
+    Form, LengthLimits, Report, View,
   },
-  util::{copy_content, current_millis},
+  util::{copy_secret, current_millis, ClipboardOptions, CopiedSecret},
 };
 
 enum AppMode {
@@ -28,76 +35,238 @@ enum AppMode {
   Add,
   Del,
   Edit,
+  Report,
+  Locked,
 }
 
-pub struct App {
-  mode: AppMode,
+// the decrypted side of the app, dropped while locked
+struct Vault {
   account_repo: AccountRepo,
   pwd_repo: PwdRepo,
+}
+
+// when to lock the vault besides a manual lock
+#[derive(Clone, Copy)]
+pub struct LockOptions {
+  pub after: Option<Duration>,
+  pub on_focus_loss: bool,
+}
+
+// what is asked of the stored passwords
+#[derive(Clone)]
+pub struct AccountOptions {
+  pub password_length: LengthLimits,
+  // a password unchanged for longer is reported by the health report
+  pub max_age: Option<Duration>,
+  // breached passwords are flagged in the table and the health report
+  pub breaches: Option<BreachList>,
+}
+
+fn unlocked(vault: &Option<Vault>) -> TecResult<&Vault> {
+  vault.as_ref().ok_or(TecError::Locked)
+}
+
+pub struct App {
+  mode: AppMode,
+  vault: Option<Vault>,
+  config_path: PathBuf,
+  auth_opts: AuthOptions,
+  lock_opts: LockOptions,
+  clipboard_opts: ClipboardOptions,
+  account_opts: AccountOptions,
+  last_event: Instant,
+  suspending: bool,
+  copied: Option<CopiedSecret>,
+  // the lock screen, while locked
+  unlock: Option<Auth>,
 
   quiting: bool,
   help_text: String,
+  // shown in place of the help text until the next key press
+  status: String,
+  warning: String,
+  // issues found in the manifest when the vault was opened
+  tampered: usize,
   account_table: AccountTable,
   view: View,
   form: Form,
+  report: Report,
   to_del: Confirm,
 }
 
 impl App {
-  pub fn build(config_path: impl AsRef<Path>, key: &[u8]) -> TecResult<Self> {
-    let db_path = config_path.as_ref().join("tecpass.db");
-    let conn = Rc::new(sqlite_conn(db_path)?);
-    let cipher = Rc::new(AesCipher::from_slice(key)?);
-    let account_repo = AccountRepo::new(conn.clone(), cipher.clone());
-    let pwd_repo = PwdRepo::new(conn, cipher);
-
+  pub fn build(
+    config_path: impl AsRef<Path>,
+    key: &[u8],
+    auth_opts: AuthOptions,
+    lock_opts: LockOptions,
+    clipboard_opts: ClipboardOptions,
+    account_opts: AccountOptions,
+  ) -> TecResult<Self> {
+    let password_length = account_opts.password_length;
     let mut app = Self {
       // account_repo: AccountRepo::new(conn),
       mode: AppMode::Table,
-      account_repo,
-      pwd_repo,
+      vault: None,
+      config_path: config_path.as_ref().to_path_buf(),
+      auth_opts,
+      lock_opts,
+      clipboard_opts,
+      account_opts,
+      last_event: Instant::now(),
+      suspending: false,
+      copied: None,
+      unlock: None,
       quiting: false,
       help_text: "".to_owned(),
+      status: "".to_owned(),
+      warning: "".to_owned(),
+      tampered: 0,
       account_table: AccountTable::default(),
       view: View::default(),
-      form: Form::default(),
+      form: Form::default().with_password_limits(password_length),
+      report: Report::default(),
       to_del: Confirm::default().with_content("To delete the selected account?"),
     };
 
-    app.change_mode(AppMode::Table);
-    app.load_accounts()?;
+    app.open(key)?;
 
     // app.change_mode(mode);
     Ok(app)
   }
 
+  fn open(&mut self, key: &[u8]) -> TecResult<()> {
+    let db_path = self.config_path.join("tecpass.db");
+    let (account_repo, pwd_repo, manifest_repo) = open_repos(db_path, key)?;
+    self.tampered = manifest_repo.verify()?.len();
+    self.vault = Some(Vault {
+      account_repo,
+      pwd_repo,
+    });
+
+    self.change_mode(AppMode::Table);
+    self.load_accounts()
+  }
+
+  // drop the key and every decrypted account until the password is entered again
+  pub(crate) fn lock(&mut self) -> TecResult<()> {
+    if self.unlock.is_some() {
+      return Ok(());
+    }
+    self.vault = None;
+    self.account_table.reset();
+    self.view.reset();
+    self.form.reset();
+    self.report.reset();
+    self.unlock = Some(Auth::build(
+      &self.config_path,
+      AuthAction::Login,
+      self.auth_opts.clone(),
+    )?);
+    self.change_mode(AppMode::Locked);
+    Ok(())
+  }
+
   pub fn on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    self.last_event = Instant::now();
+    self.status.clear();
     // Press `Ctrl-c` to quit
     if key_event.kind == KeyEventKind::Press
       && key_event.modifiers == KeyModifiers::CONTROL
       && key_event.code == KeyCode::Char('c')
     {
       self.quiting = true;
+      // don't leave a copied password behind
+      if let Some(copied) = self.copied.take() {
+        let res = copied.clear();
+        return self.report(res);
+      }
       return Ok(());
     }
-    match self.mode {
-      AppMode::Table => self.table_on_key_envent(key_event)?,
-      AppMode::View => self.view_on_key_event(key_event)?,
-      AppMode::Add => self.add_on_key_event(key_event)?,
-      AppMode::Del => self.del_on_key_event(key_event)?,
-      AppMode::Edit => self.edit_on_key_event(key_event)?,
+    // Press `Ctrl-z` to lock and suspend
+    if key_event.kind == KeyEventKind::Press
+      && key_event.modifiers == KeyModifiers::CONTROL
+      && key_event.code == KeyCode::Char('z')
+    {
+      self.lock()?;
+      self.suspending = true;
+      return Ok(());
     }
+    let res = match self.mode {
+      AppMode::Table => self.table_on_key_envent(key_event),
+      AppMode::View => self.view_on_key_event(key_event),
+      AppMode::Add => self.add_on_key_event(key_event),
+      AppMode::Del => self.del_on_key_event(key_event),
+      AppMode::Edit => self.edit_on_key_event(key_event),
+      AppMode::Report => self.report_on_key_event(key_event),
+      AppMode::Locked => self.locked_on_key_event(key_event),
+    };
+
+    self.report(res)
+  }
+
+  // a failing clipboard or hash list, or a write refused by or a row unreadable in a
+  // tampered vault, is shown in the status bar, rather than quitting the app
+  fn report(&mut self, res: TecResult<()>) -> TecResult<()> {
+    match res {
+      Err(
+        err @ (TecError::Clipboard(_)
+        | TecError::InvalidHashList(_)
+        | TecError::Tampered
+        | TecError::InvalidCiphertext(_)),
+      ) => {
+        self.status = err.to_string();
+        Ok(())
+      }
+      res => res,
+    }
+  }
 
+  pub(crate) fn on_tick(&mut self) -> TecResult<()> {
+    if let (Some(copied), Some(clear_after)) = (&self.copied, self.clipboard_opts.clear_after) {
+      if copied.elapsed() >= clear_after {
+        let res = copied.clear();
+        self.copied = None;
+        self.report(res)?;
+      }
+    }
+
+    if let Some(unlock) = self.unlock.as_mut() {
+      unlock.on_tick();
+    } else if self
+      .lock_opts
+      .after
+      .is_some_and(|idle| self.last_event.elapsed() >= idle)
+    {
+      self.lock()?;
+    }
+    Ok(())
+  }
+
+  pub(crate) fn on_focus_lost(&mut self) -> TecResult<()> {
+    if self.lock_opts.on_focus_loss {
+      self.lock()?;
+    }
     Ok(())
   }
 
-  pub(crate) fn on_tick(&self) {}
+  // whether the terminal should be handed back to the shell
+  pub(crate) fn take_suspending(&mut self) -> bool {
+    std::mem::take(&mut self.suspending)
+  }
 
   pub(crate) fn quit(&self) -> bool {
     self.quiting
   }
 
   fn table_on_key_envent(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    // Press `Ctrl-l` to lock
+    if key_event.kind == KeyEventKind::Press
+      && key_event.modifiers == KeyModifiers::CONTROL
+      && key_event.code == KeyCode::Char('l')
+    {
+      return self.lock();
+    }
     if !self.account_table.is_querying() {
       match key_event {
         KeyEvent {
@@ -105,21 +274,14 @@ impl App {
           kind: KeyEventKind::Press,
           ..
         } => match code {
-          KeyCode::Enter | KeyCode::Char('l') => {
-            if let Some(account) = self.account_table.selected() {
-              self.view.load_account(account.clone());
-              let pwds = self.pwd_repo.query(account.id)?;
-              self.view.load_pwds(pwds);
-              self.change_mode(AppMode::View);
-            }
-          }
+          KeyCode::Enter | KeyCode::Char('l') => self.open_view()?,
           KeyCode::Char('a') => {
             self.form.reset();
             self.change_mode(AppMode::Add);
           }
           KeyCode::Char('e') => {
             if let Some(acc) = self.account_table.selected() {
-              let pwds = self.pwd_repo.query(acc.id)?;
+              let pwds = unlocked(&self.vault)?.pwd_repo.query(acc.id)?;
               self.form.load_account(acc, pwds.get(0))?;
               self.change_mode(AppMode::Edit);
             }
@@ -130,6 +292,10 @@ impl App {
           KeyCode::Char('c') => {
             self.copy()?;
           }
+          KeyCode::Char('h') => {
+            let res = self.open_report();
+            self.report(res)?;
+          }
           _ => {}
         },
         _ => {}
@@ -139,6 +305,52 @@ impl App {
     Ok(())
   }
 
+  fn open_view(&mut self) -> TecResult<()> {
+    if let Some(account) = self.account_table.selected() {
+      self.view.load_account(account.clone());
+      let pwds = unlocked(&self.vault)?.pwd_repo.query(account.id)?;
+      self.view.load_pwds(pwds);
+      self.change_mode(AppMode::View);
+    }
+    Ok(())
+  }
+
+  fn open_report(&mut self) -> TecResult<()> {
+    let vault = unlocked(&self.vault)?;
+    let findings = audit(
+      &vault.account_repo,
+      &vault.pwd_repo,
+      self.account_opts.max_age,
+      self.account_opts.breaches.as_ref(),
+    )?;
+    self.report.load(findings);
+    self.change_mode(AppMode::Report);
+    Ok(())
+  }
+
+  fn report_on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    if key_event.kind != KeyEventKind::Press {
+      return Ok(());
+    }
+    match key_event.code {
+      KeyCode::Esc | KeyCode::Char('q') => {
+        self.report.reset();
+        self.change_mode(AppMode::Table);
+      }
+      // jump to the account, without a filter that could hide it
+      KeyCode::Enter | KeyCode::Char('l') => {
+        if let Some(aid) = self.report.selected() {
+          self.report.reset();
+          self.load_accounts()?;
+          self.account_table.select_by_aid(aid);
+          self.open_view()?;
+        }
+      }
+      _ => self.report.on_key_event(key_event)?,
+    }
+    Ok(())
+  }
+
   fn view_on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
     match key_event {
       KeyEvent {
@@ -146,10 +358,14 @@ impl App {
         kind: KeyEventKind::Press,
         ..
       } => {
+        self.view.reset();
         self.change_mode(AppMode::Table);
       }
       _ => {
         self.view.on_key_event(key_event)?;
+        if let Some(copied) = self.view.take_copied() {
+          self.copied = Some(copied);
+        }
       }
     }
     Ok(())
@@ -161,6 +377,7 @@ impl App {
         kind: KeyEventKind::Press,
         ..
       } => {
+        self.form.reset();
         self.change_mode(AppMode::Table);
       }
       KeyEvent {
@@ -177,14 +394,14 @@ impl App {
             created: current,
             changed: current,
           };
-          let aid = self.account_repo.add(&acc)?;
+          let aid = unlocked(&self.vault)?.account_repo.add(&acc)?;
           let pwd = Pwd {
             id: 0,
             aid,
-            password: self.form.password().to_string(),
+            password: SecretString::new(self.form.password().to_string()),
             created: current,
           };
-          self.pwd_repo.add(&pwd)?;
+          unlocked(&self.vault)?.pwd_repo.add(&pwd)?;
 
           self.load_accounts()?;
           self.account_table.select_by_aid(aid);
@@ -205,6 +422,7 @@ impl App {
         kind: KeyEventKind::Press,
         ..
       } => {
+        self.form.reset();
         self.change_mode(AppMode::Table);
         return Ok(());
       }
@@ -224,16 +442,16 @@ impl App {
               created: current,
               changed: current,
             };
-            self.account_repo.update(&acc)?;
+            unlocked(&self.vault)?.account_repo.update(&acc)?;
             let pwd = Pwd {
               id: 0,
               aid: selected.id,
-              password: self.form.password().to_string(),
+              password: SecretString::new(self.form.password().to_string()),
               created: current,
             };
-            self.pwd_repo.add(&pwd)?;
+            unlocked(&self.vault)?.pwd_repo.add(&pwd)?;
 
-            // self.account_table.load(self.account_repo.all()?);
+            // self.account_table.load(unlocked(&self.vault)?.account_repo.all()?);
             self.load_accounts()?;
             self.account_table.select_by_aid(aid);
 
@@ -262,9 +480,9 @@ impl App {
       } => {
         if self.to_del.confirm() {
           if let Some(acc) = self.account_table.selected() {
-            self.account_repo.delete(acc.id)?;
-            self.pwd_repo.delete(acc.id)?;
-            self.account_table.load(self.account_repo.all()?);
+            unlocked(&self.vault)?.account_repo.delete(acc.id)?;
+            unlocked(&self.vault)?.pwd_repo.delete(acc.id)?;
+            self.load_accounts()?;
           }
         }
         self.change_mode(AppMode::Table);
@@ -274,12 +492,23 @@ impl App {
     Ok(())
   }
 
+  fn locked_on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    if let Some(unlock) = self.unlock.as_mut() {
+      unlock.on_key_event(key_event)?;
+      if let Some(key) = unlock.key() {
+        self.unlock = None;
+        self.open(&key)?;
+      }
+    }
+    Ok(())
+  }
+
   fn change_mode(&mut self, mode: AppMode) {
     self.mode = mode;
     match self.mode {
       AppMode::Table => {
         self.help_text =
-          "/: filter, a: add, e: edit, d: delete, c: copy password, j: next, k: prev, l/enter: view, ctrl-c: quit"
+          "/: filter, a: add, e: edit, d: delete, c: copy password, j: next, k: prev, l/enter: view, h: health report, ctrl-l: lock, ctrl-c: quit"
             .to_owned()
       }
       AppMode::View => {
@@ -287,41 +516,100 @@ impl App {
       }
       AppMode::Add => {
         self.help_text =
-          "Eidt Account - down/ctrl-j: next, up/ctrl-k: prev, ctrl-x: show/hide passwords, ctrl-v: paste, esc: back".to_owned()
+          "Eidt Account - down/ctrl-j: next, up/ctrl-k: prev, ctrl-x: show/hide passwords, ctrl-g: generate password, ctrl-v: paste, esc: back".to_owned()
       }
       AppMode::Del => self.help_text = "Delete Account - esc: back".to_owned(),
       AppMode::Edit => {
         self.help_text =
-          "Edit Account - ctrl-j: next, ctrl-k: prev, ctrl-x: show/hide passwords, ctrl-v: paste, esc: back".to_owned()
+          "Edit Account - ctrl-j: next, ctrl-k: prev, ctrl-x: show/hide passwords, ctrl-g: generate password, ctrl-v: paste, esc: back".to_owned()
       }
+      AppMode::Report => {
+        self.help_text =
+          "Health Report - l/enter: go to account, j: next, k: prev, q/esc: back".to_owned()
+      }
+      AppMode::Locked => self.help_text = "Locked - enter: unlock, ctrl-c: quit".to_owned(),
     }
   }
 
   fn copy(&mut self) -> TecResult<()> {
     if let Some(account) = self.account_table.selected() {
-      let pwds = self.pwd_repo.query(account.id)?;
+      let pwds = unlocked(&self.vault)?.pwd_repo.query(account.id)?;
       if let Some(pwd) = pwds.get(0) {
-        copy_content(pwd.password.as_bytes())?;
+        self.copied = Some(copy_secret(pwd.password.as_bytes())?);
       }
     }
     Ok(())
   }
 
   fn load_accounts(&mut self) -> TecResult<()> {
-    let accounts = self.account_repo.all()?;
+    let vault = unlocked(&self.vault)?;
+    let (accounts, unreadable) = vault.account_repo.all_readable()?;
+    self.warning = warning(self.tampered, unreadable.len());
+    let breaches = self
+      .account_opts
+      .breaches
+      .as_ref()
+      .map(|list| breached(&accounts, &vault.pwd_repo, list));
     self.account_table.load(accounts);
+    if let Some(res) = breaches {
+      match res {
+        Ok(ids) => self.account_table.set_breached(ids),
+        Err(err) => return self.report(Err(err)),
+      }
+    }
     Ok(())
   }
 }
 
+fn warning(tampered: usize, unreadable: usize) -> String {
+  let mut warning = String::new();
+  if tampered > 0 {
+    warning = format!(
+      "WARNING: the vault has been tampered with ({tampered} issues), run `tecpass --verify` for details"
+    );
+  }
+  if unreadable > 0 {
+    let sep = if warning.is_empty() {
+      "WARNING: "
+    } else {
+      ", "
+    };
+    warning += &format!("{sep}{unreadable} accounts could not be decrypted and are hidden");
+  }
+  warning
+}
+
 pub fn draw_app(f: &mut Frame, app: &mut App) {
-  let [main_area, help_area] = Layout::vertical([
+  let warning_height = if app.warning.is_empty() { 0 } else { 1 };
+  let [warning_area, main_area, help_area] = Layout::vertical([
+    Constraint::Length(warning_height),
     Constraint::Min(3),
     // Constraint::Length(1),
     Constraint::Length(1),
   ])
   .areas(f.size());
 
+  if !app.warning.is_empty() {
+    let warning = Line::styled(&app.warning, Style::new().bold().white().on_red());
+    f.render_widget(warning, warning_area);
+  }
+
+  if let Some(unlock) = app.unlock.as_mut() {
+    let [_, lock_area, _] = Layout::vertical([
+      Constraint::Min(0),
+      Constraint::Length(6),
+      Constraint::Min(0),
+    ])
+    .areas(main_area);
+    let lock_area = centered_rect(60, 100, lock_area);
+    let block = Block::new().title("Locked").borders(Borders::ALL);
+    let inner_area = block.inner(lock_area);
+    f.render_widget(block, lock_area);
+    draw_auth(f, unlock, inner_area);
+    draw_help(f, app, help_area);
+    return;
+  }
+
   draw_account_table(f, &mut app.account_table, main_area);
 
   let pop_rect = centered_rect(60, 60, main_area);
@@ -337,14 +625,40 @@ pub fn draw_app(f: &mut Frame, app: &mut App) {
     AppMode::Add => draw_form(f, &app.form, pop_rect),
     AppMode::Edit => draw_form(f, &app.form, pop_rect),
     AppMode::Del => draw_confirm(f, &app.to_del, centered_rect(60, 6, main_area)),
-    AppMode::Table => {}
+    AppMode::Report => draw_report(f, &mut app.report, pop_rect),
+    AppMode::Table | AppMode::Locked => {}
   }
   // match app.mode {
   //   AppMode::Table => {
   //     draw_account_table(f, &mut app.account_table, main_area, search_area);
   //   } // _ => {}
   // }
-  f.render_widget(Line::raw(&app.help_text), help_area);
+  draw_help(f, app, help_area);
+}
+
+// the clipboard countdown is kept on the right, so that a long help text can't hide it
+fn draw_help(f: &mut Frame, app: &App, area: Rect) {
+  let mut help_area = area;
+  if let (Some(copied), Some(clear_after)) = (&app.copied, app.clipboard_opts.clear_after) {
+    let remaining = clear_after.saturating_sub(copied.elapsed());
+    let countdown = Line::from(format!(
+      " clipboard clears in {}s",
+      remaining.as_millis().div_ceil(1000)
+    ))
+    .yellow();
+    let [rest, countdown_area] = Layout::horizontal([
+      Constraint::Min(0),
+      Constraint::Length(countdown.width() as u16),
+    ])
+    .areas(area);
+    f.render_widget(countdown, countdown_area);
+    help_area = rest;
+  }
+  if app.status.is_empty() {
+    f.render_widget(Line::raw(&app.help_text), help_area);
+  } else {
+    f.render_widget(Line::raw(&app.status).red(), help_area);
+  }
 }
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
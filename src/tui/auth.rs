@@ -1,33 +1,85 @@
 use std::path::{Path, PathBuf};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use ratatui::Frame;
+use ratatui::{
+  layout::{Constraint, Layout, Rect},
+  style::{Style, Stylize},
+  text::Line,
+  widgets::{Paragraph, Wrap},
+  Frame,
+};
 
-use crate::{common::TecResult, db::KeyStore};
+use crate::{
+  cipher::Argon2Params,
+  common::{SecretBytes, SecretString, TecError, TecResult},
+  db::{recover_key, resume_rotation, rotate_key, sqlite_conn, unlock_key, Attempts, KeyStore},
+  repo::init_manifest,
+};
 
-use super::module::{draw_confirm_password, draw_input, ConfirmPassword, Input};
+use super::module::{draw_confirm_password, draw_input, ConfirmPassword, Input, LengthLimits};
+
+// how the key is unlocked, shared by the login and the lock screen
+#[derive(Clone)]
+pub struct AuthOptions {
+  pub keyfile: Option<PathBuf>,
+  pub max_attempts: Option<u32>,
+  // only checked when a new master password is set
+  pub master_length: LengthLimits,
+}
+
+// what to do once the current password is verified
+#[derive(Clone, Copy)]
+pub enum AuthAction {
+  Login,
+  ChangePassword,
+  RotateKey,
+  Reseal(Argon2Params),
+  NewRecovery,
+}
 
 enum AuthMode {
   Reg,
   Login,
+  Change,
+  Recover,
+  ShowRecovery,
 }
 
 pub struct Auth {
   mode: AuthMode,
+  action: AuthAction,
   key_store: KeyStore<PathBuf>,
+  db_path: PathBuf,
+  attempts: Attempts,
+  // failed logins in a row after which only the recovery code is accepted
+  max_attempts: Option<u32>,
+  throttled: bool,
 
   quiting: bool,
-  key: Option<Vec<u8>>,
+  key: Option<SecretBytes>,
+  // the key unlocked by the old password or the recovery code while waiting for the new
+  // password, or while the new recovery code is shown
+  unlocked: Option<SecretBytes>,
+  recovery_code: SecretString,
 
   login: Input,
   reg: ConfirmPassword,
+  change: ConfirmPassword,
+  recover: Input,
 }
 
 impl Auth {
-  pub fn build(config_path: impl AsRef<Path>) -> TecResult<Self> {
+  pub fn build(
+    config_path: impl AsRef<Path>,
+    action: AuthAction,
+    opts: AuthOptions,
+  ) -> TecResult<Self> {
     let key_path = config_path.as_ref().join("tecpass.sealed.key");
+    let db_path = config_path.as_ref().join("tecpass.db");
 
-    let key_store = KeyStore::new(key_path);
+    let key_store = KeyStore::new(key_path).with_keyfile(opts.keyfile);
+    resume_rotation(&sqlite_conn(&db_path)?, &key_store)?;
+    let attempts = key_store.attempts()?;
     let mode = {
       if key_store.has_key() {
         AuthMode::Login
@@ -37,16 +89,34 @@ impl Auth {
     };
     let mut auth = Self {
       key_store,
+      db_path,
+      attempts,
+      max_attempts: opts.max_attempts,
+      throttled: false,
+      action,
       mode: AuthMode::Login,
       quiting: false,
       key: None,
+      unlocked: None,
+      recovery_code: SecretString::default(),
       login: Input::default()
         .with_mask()
         .with_label("password: ")
-        .with_min(8)
-        .with_max(32)
+        .with_min(1)
+        .with_max(usize::MAX)
+        .with_active(),
+      reg: ConfirmPassword::default()
+        .with_title("Register password")
+        .with_limits(opts.master_length)
+        .with_strength_check(),
+      change: ConfirmPassword::default()
+        .with_title("Change password")
+        .with_limits(opts.master_length)
+        .with_strength_check(),
+      recover: Input::default()
+        .with_label("recovery code: ")
+        .with_min(1)
         .with_active(),
-      reg: ConfirmPassword::default().with_title("Register password"),
     };
     auth.change_mode(mode);
     Ok(auth)
@@ -56,10 +126,39 @@ impl Auth {
     self.quiting
   }
 
-  pub fn key(&mut self) -> Option<Vec<u8>> {
+  pub fn key(&mut self) -> Option<SecretBytes> {
     self.key.take()
   }
 
+  pub(crate) fn on_tick(&mut self) {
+    if !matches!(self.mode, AuthMode::Login) {
+      return;
+    }
+    if self.locked_out() {
+      self
+        .login
+        .set_msg("too many failed attempts, use the recovery code (ctrl-r)");
+      return;
+    }
+    let remaining = self.attempts.remaining();
+    if !remaining.is_zero() {
+      self.throttled = true;
+      self.login.set_msg(format!(
+        "too many failed attempts, try again in {}s",
+        remaining.as_millis().div_ceil(1000)
+      ));
+    } else if self.throttled {
+      self.throttled = false;
+      self.login.set_msg("");
+    }
+  }
+
+  fn locked_out(&self) -> bool {
+    self
+      .max_attempts
+      .is_some_and(|max| self.attempts.failures() >= max)
+  }
+
   pub fn on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
     // Press `Ctrl-c` to quit
     if key_event.kind == KeyEventKind::Press
@@ -72,21 +171,58 @@ impl Auth {
     match self.mode {
       AuthMode::Login => self.login_on_key_event(key_event)?,
       AuthMode::Reg => self.reg_on_key_envent(key_event)?,
+      AuthMode::Change => self.change_on_key_event(key_event)?,
+      AuthMode::Recover => self.recover_on_key_event(key_event)?,
+      AuthMode::ShowRecovery => self.show_recovery_on_key_event(key_event),
     }
 
     Ok(())
   }
 
   fn login_on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    // Press `Ctrl-r` if the password is forgotten
+    if key_event.kind == KeyEventKind::Press
+      && key_event.modifiers == KeyModifiers::CONTROL
+      && key_event.code == KeyCode::Char('r')
+    {
+      if self.key_store.has_recovery() {
+        self.login.reset();
+        self.change_mode(AuthMode::Recover);
+      } else {
+        self.login.set_msg("no recovery code was set up");
+      }
+      return Ok(());
+    }
     self.login.on_key_event(key_event)?;
     if key_event.kind == KeyEventKind::Press && key_event.code == KeyCode::Enter {
+      // wait for the back-off without even checking the password
+      if self.locked_out() || !self.attempts.remaining().is_zero() {
+        self.on_tick();
+        return Ok(());
+      }
       if self.login.validate() {
-        let pwd = self.login.content();
-        let res = self.key_store.get_key(pwd.as_bytes());
-        if let Ok(key) = res {
-          self.key = Some(key);
-        } else {
-          self.login.set_msg("wrong password");
+        let pwd = SecretString::new(self.login.content().to_owned());
+        let conn = sqlite_conn(&self.db_path)?;
+        match unlock_key(&conn, &self.key_store, pwd.as_bytes()) {
+          Ok(key) => {
+            self.attempts.reset()?;
+            match self.action {
+              AuthAction::ChangePassword => {
+                self.unlocked = Some(key);
+                self.change_mode(AuthMode::Change);
+              }
+              _ => self.run_action(key, pwd.as_bytes())?,
+            }
+            self.login.reset();
+          }
+          // only a key that fails to decrypt is a wrong password, anything else, e.g. a
+          // wrong keyfile or a key file from a newer version, doesn't count as an attempt
+          Err(TecError::AESError(_)) => {
+            self.attempts.fail()?;
+            self.login.set_msg("wrong password");
+            self.on_tick();
+          }
+          Err(err) => self.login.set_msg(err.to_string()),
         }
       }
     }
@@ -98,22 +234,139 @@ impl Auth {
     if key_event.kind == KeyEventKind::Press && key_event.code == KeyCode::Enter {
       if self.reg.validate() {
         let pwd = self.reg.content();
-        self.key_store.set_key(pwd.as_bytes())?;
+        let key = self.key_store.set_key(pwd.as_bytes())?;
+        // sealed as having a manifest, like every key sealed since
+        init_manifest(&sqlite_conn(&self.db_path)?, &key)?;
+        self.attempts.reset()?;
+        self.reg.reset();
+        self.recovery_code = SecretString::new(self.key_store.set_recovery(&key)?);
+        self.change_mode(AuthMode::ShowRecovery);
+      }
+    }
+    Ok(())
+  }
+
+  fn change_on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    self.change.on_key_event(key_event)?;
+    if key_event.kind == KeyEventKind::Press
+      && key_event.code == KeyCode::Enter
+      && self.change.validate()
+    {
+      if let Some(key) = self.unlocked.take() {
+        let pwd = SecretString::new(self.change.content().to_owned());
+        // sealed right away, the action that follows a recovery may still fail. a re-seal
+        // would only do it twice.
+        if !matches!(self.action, AuthAction::Reseal(_)) {
+          self.key_store.seal_key(&key, pwd.as_bytes())?;
+        }
+        self.change.reset();
+        self.run_action(key, pwd.as_bytes())?;
+      }
+    }
+    Ok(())
+  }
+
+  // once the key is unlocked by the password, or by the recovery code and a new password
+  fn run_action(&mut self, key: SecretBytes, pwd: &[u8]) -> TecResult<()> {
+    match self.action {
+      AuthAction::Login | AuthAction::ChangePassword => self.key = Some(key),
+      AuthAction::RotateKey => {
+        // the recovery copy of the old key is removed by the rotation
+        let had_recovery = self.key_store.has_recovery();
+        let mut conn = sqlite_conn(&self.db_path)?;
+        let key = rotate_key(&mut conn, &self.key_store, &key, pwd)?;
+        if had_recovery {
+          self.show_recovery(key)?;
+        } else {
+          self.key = Some(key);
+        }
+      }
+      AuthAction::Reseal(params) => {
+        self.key_store.seal_key_with(&key, pwd, params)?;
+        self.key = Some(key);
+      }
+      AuthAction::NewRecovery => self.show_recovery(key)?,
+    }
+    Ok(())
+  }
+
+  fn recover_on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    if key_event.kind != KeyEventKind::Press {
+      return Ok(());
+    }
+    match key_event.code {
+      KeyCode::Esc => {
+        self.recover.reset();
         self.change_mode(AuthMode::Login);
       }
+      KeyCode::Enter => {
+        if self.recover.validate() {
+          let conn = sqlite_conn(&self.db_path)?;
+          let res = recover_key(&conn, &self.key_store, self.recover.content());
+          match res {
+            Ok(key) => {
+              self.attempts.reset()?;
+              self.recover.reset();
+              self.unlocked = Some(key);
+              self.change_mode(AuthMode::Change);
+            }
+            Err(TecError::AESError(_)) => self.recover.set_msg("wrong recovery code"),
+            Err(err) => self.recover.set_msg(err.to_string()),
+          }
+        }
+      }
+      _ => self.recover.on_key_event(key_event)?,
     }
     Ok(())
   }
 
+  fn show_recovery(&mut self, key: SecretBytes) -> TecResult<()> {
+    self.recovery_code = SecretString::new(self.key_store.set_recovery(&key)?);
+    self.unlocked = Some(key);
+    self.change_mode(AuthMode::ShowRecovery);
+    Ok(())
+  }
+
+  fn show_recovery_on_key_event(&mut self, key_event: KeyEvent) {
+    if key_event.kind == KeyEventKind::Press && key_event.code == KeyCode::Enter {
+      self.recovery_code = SecretString::default();
+      match self.unlocked.take() {
+        Some(key) => self.key = Some(key),
+        None => self.change_mode(AuthMode::Login),
+      }
+    }
+  }
+
   fn change_mode(&mut self, mode: AuthMode) {
     self.mode = mode
   }
 }
-pub fn draw_auth(f: &mut Frame, auth: &mut Auth) {
-  let area = f.size();
-
+pub fn draw_auth(f: &mut Frame, auth: &mut Auth, area: Rect) {
   match auth.mode {
-    AuthMode::Login => draw_input(f, &auth.login, area),
+    AuthMode::Login => {
+      let [input_area, help_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(area);
+      draw_input(f, &auth.login, input_area);
+      if auth.key_store.has_recovery() {
+        f.render_widget(Line::raw("ctrl-r: forgot password").dim(), help_area);
+      }
+    }
     AuthMode::Reg => draw_confirm_password(f, &auth.reg, area),
+    AuthMode::Change => draw_confirm_password(f, &auth.change, area),
+    AuthMode::Recover => draw_input(f, &auth.recover, area),
+    AuthMode::ShowRecovery => {
+      let lines = vec![
+        Line::from(vec![
+          "recovery code: ".bold(),
+          auth.recovery_code.as_str().into(),
+        ]),
+        Line::styled(
+          "Write it down and keep it safe, it is shown only once. It unlocks the vault if the password is forgotten.",
+          Style::new().yellow(),
+        ),
+        Line::raw("press enter to continue"),
+      ];
+      f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), area);
+    }
   }
 }
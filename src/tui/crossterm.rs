@@ -1,11 +1,17 @@
 use std::{
   io::{self, stdout},
   path::Path,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
   time::{Duration, Instant},
 };
 
 use crossterm::{
-  event::{self, DisableMouseCapture, EnableMouseCapture, Event},
+  event::{
+    self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event,
+  },
   execute,
   terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -14,14 +20,27 @@ use ratatui::{
   Terminal, TerminalOptions, Viewport,
 };
 
-use crate::common::TecResult;
+use signal_hook::{
+  consts::{SIGHUP, SIGTSTP},
+  flag, low_level,
+};
+
+use crate::common::{SecretBytes, TecResult};
 
 use super::{
-  app::{draw_app, App},
-  auth::{draw_auth, Auth},
+  app::{draw_app, AccountOptions, App, LockOptions},
+  auth::{draw_auth, Auth, AuthAction, AuthOptions},
+  util::{init_clipboard, ClipboardOptions},
 };
 
-pub fn auth(tick_rate: Duration, config_path: impl AsRef<Path>) -> TecResult<Option<Vec<u8>>> {
+pub fn auth(
+  tick_rate: Duration,
+  config_path: impl AsRef<Path>,
+  action: AuthAction,
+  opts: AuthOptions,
+) -> TecResult<Option<SecretBytes>> {
+  // before the terminal is set up, which a failure would leave in raw mode
+  let auth = Auth::build(config_path, action, opts)?;
   enable_raw_mode()?;
   let backend = CrosstermBackend::new(io::stdout());
   let mut terminal = Terminal::with_options(
@@ -31,7 +50,6 @@ pub fn auth(tick_rate: Duration, config_path: impl AsRef<Path>) -> TecResult<Opt
     },
   )?;
 
-  let auth = Auth::build(config_path)?;
   let res = run_auth(&mut terminal, auth, tick_rate);
   disable_raw_mode()?;
   res
@@ -41,10 +59,10 @@ fn run_auth<B: Backend>(
   terminal: &mut Terminal<B>,
   mut auth: Auth,
   tick_rate: Duration,
-) -> TecResult<Option<Vec<u8>>> {
+) -> TecResult<Option<SecretBytes>> {
   let mut last_tick = Instant::now();
   loop {
-    terminal.draw(|f| draw_auth(f, &mut auth))?;
+    terminal.draw(|f| draw_auth(f, &mut auth, f.size()))?;
 
     let timeout = tick_rate.saturating_sub(last_tick.elapsed());
     if event::poll(timeout)? {
@@ -54,6 +72,7 @@ fn run_auth<B: Backend>(
       }
     }
     if last_tick.elapsed() >= tick_rate {
+      auth.on_tick();
       last_tick = Instant::now();
     }
 
@@ -68,24 +87,53 @@ fn run_auth<B: Backend>(
   }
 }
 
-pub fn run(tick_rate: Duration, config_path: impl AsRef<Path>, key: Vec<u8>) -> TecResult<()> {
+pub fn run(
+  tick_rate: Duration,
+  config_path: impl AsRef<Path>,
+  key: SecretBytes,
+  opts: AuthOptions,
+  lock_opts: LockOptions,
+  clipboard_opts: ClipboardOptions,
+  account_opts: AccountOptions,
+) -> TecResult<()> {
+  let signals = Signals::register()?;
+  init_clipboard(&clipboard_opts)?;
+
+  // create the app before the terminal is set up, which a failure would leave in raw mode
+  // and the alternate screen
+  let app = App::build(
+    config_path,
+    &key,
+    opts,
+    lock_opts,
+    clipboard_opts,
+    account_opts,
+  )?;
+  // the app holds the key in its cipher, which is dropped while locked
+  drop(key);
+
   // setup terminal
   enable_raw_mode()?;
   let mut stdout = io::stdout();
-  execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+  execute!(
+    stdout,
+    EnterAlternateScreen,
+    EnableMouseCapture,
+    EnableFocusChange
+  )?;
   let backend = CrosstermBackend::new(stdout);
   let mut terminal = Terminal::new(backend)?;
 
-  // create and run app
-  let app = App::build(config_path, &key)?;
-  let res = run_app(&mut terminal, app, tick_rate);
+  // run app
+  let res = run_app(&mut terminal, app, tick_rate, &signals);
 
   // restore terminal
   disable_raw_mode()?;
   execute!(
     terminal.backend_mut(),
     LeaveAlternateScreen,
-    DisableMouseCapture
+    DisableMouseCapture,
+    DisableFocusChange
   )?;
   terminal.show_cursor()?;
 
@@ -101,6 +149,7 @@ fn run_app<B: Backend>(
   terminal: &mut Terminal<B>,
   mut app: App,
   tick_rate: Duration,
+  signals: &Signals,
 ) -> TecResult<()> {
   let mut last_tick = Instant::now();
   loop {
@@ -110,11 +159,20 @@ fn run_app<B: Backend>(
     if event::poll(timeout)? {
       match event::read()? {
         Event::Key(key_event) => app.on_key_event(key_event)?,
+        Event::FocusLost => app.on_focus_lost()?,
         _ => {}
       }
     }
+    if signals.hangup.load(Ordering::Relaxed) {
+      app.lock()?;
+      return Ok(());
+    }
+    if signals.suspend.swap(false, Ordering::Relaxed) || app.take_suspending() {
+      app.lock()?;
+      suspend(terminal)?;
+    }
     if last_tick.elapsed() >= tick_rate {
-      app.on_tick();
+      app.on_tick()?;
       last_tick = Instant::now();
     }
     if app.quit() {
@@ -123,3 +181,45 @@ fn run_app<B: Backend>(
   }
   // Ok(())
 }
+
+// set by the signal handlers, checked on every loop
+struct Signals {
+  suspend: Arc<AtomicBool>,
+  hangup: Arc<AtomicBool>,
+}
+
+impl Signals {
+  fn register() -> TecResult<Self> {
+    let signals = Self {
+      suspend: Arc::new(AtomicBool::new(false)),
+      hangup: Arc::new(AtomicBool::new(false)),
+    };
+    flag::register(SIGTSTP, Arc::clone(&signals.suspend))?;
+    flag::register(SIGHUP, Arc::clone(&signals.hangup))?;
+    Ok(signals)
+  }
+}
+
+// hand the terminal back to the shell until the process is continued
+fn suspend<B: Backend>(terminal: &mut Terminal<B>) -> TecResult<()> {
+  disable_raw_mode()?;
+  execute!(
+    io::stdout(),
+    LeaveAlternateScreen,
+    DisableMouseCapture,
+    DisableFocusChange
+  )?;
+  terminal.show_cursor()?;
+
+  low_level::emulate_default_handler(SIGTSTP)?;
+
+  enable_raw_mode()?;
+  execute!(
+    io::stdout(),
+    EnterAlternateScreen,
+    EnableMouseCapture,
+    EnableFocusChange
+  )?;
+  terminal.clear()?;
+  Ok(())
+}
@@ -4,4 +4,8 @@ mod crossterm;
 mod module;
 mod util;
 
+pub use app::{AccountOptions, LockOptions};
+pub use auth::{AuthAction, AuthOptions};
 pub use crossterm::{auth, run};
+pub use module::LengthLimits;
+pub use util::{current_millis, init_clipboard, millis2string, ClipboardKind, ClipboardOptions};
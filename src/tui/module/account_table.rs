@@ -93,6 +93,8 @@ pub struct AccountTable {
   state: TableState,
   query_content: String,
   symbol: String,
+  // ids of the accounts whose password is on the breach list
+  breached: Vec<u32>,
 }
 
 impl Default for AccountTable {
@@ -103,6 +105,7 @@ impl Default for AccountTable {
       state: TableState::default().with_selected(Some(0)),
       query_content: "".to_owned(),
       symbol: "❯".into(),
+      breached: vec![],
     }
   }
 }
@@ -144,6 +147,17 @@ impl AccountTable {
   pub fn load(&mut self, accounts: Vec<Account>) {
     self.items.load(accounts);
     self.query.reset();
+    self.breached.clear();
+  }
+
+  pub fn set_breached(&mut self, ids: Vec<u32>) {
+    self.breached = ids;
+  }
+
+  pub(crate) fn reset(&mut self) {
+    self.load(vec![]);
+    self.query_content.clear();
+    self.state.select(Some(0));
   }
 
   pub fn is_querying(&self) -> bool {
@@ -186,14 +200,25 @@ pub fn draw_account_table(f: &mut Frame, at: &mut AccountTable, area: Rect) {
   let rows: Vec<Row> = at
     .items
     .iter()
-    .map(|a| Row::new(vec![a.url(), a.username()]))
+    .map(|a| {
+      if at.breached.contains(&a.id) {
+        Row::new(vec![a.url(), a.username(), "breached"]).style(Style::new().red())
+      } else {
+        Row::new(vec![a.url(), a.username(), ""])
+      }
+    })
     .collect();
-  let widths = [Constraint::Min(10), Constraint::Min(5)];
+  let widths = [
+    Constraint::Min(10),
+    Constraint::Min(5),
+    Constraint::Length(8),
+  ];
   let table = Table::new(rows, widths)
     .column_spacing(1)
     .style(Style::new().blue())
     .header(
-      Row::new(vec!["url", "username"]).style(Style::new().bg(Color::LightYellow).fg(Color::Black)),
+      Row::new(vec!["url", "username", ""])
+        .style(Style::new().bg(Color::LightYellow).fg(Color::Black)),
     )
     .style(Style::new().bold())
     .block(Block::default())
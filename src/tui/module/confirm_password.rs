@@ -7,14 +7,17 @@ use ratatui::{
   Frame,
 };
 
-use crate::common::TecResult;
+use crate::{common::TecResult, strength::estimate};
 
-use super::{draw_input, Input};
+use super::{draw_input, Input, LengthLimits};
 
 pub struct ConfirmPassword {
   title: String,
   password: Input,
   confirm: Input,
+  // warn once about a weak password, enter again keeps it
+  check_strength: bool,
+  weak_warned: bool,
   // msg: String,
 }
 
@@ -25,14 +28,14 @@ impl Default for ConfirmPassword {
       password: Input::default()
         .with_mask()
         .with_label("password: ")
-        .with_min(8)
-        .with_max(32)
+        .with_limits(LengthLimits::MASTER_PASSWORD)
         .with_active(),
       confirm: Input::default()
         .with_mask()
         .with_label("confirm: ")
-        .with_min(8)
-        .with_max(32),
+        .with_limits(LengthLimits::MASTER_PASSWORD),
+      check_strength: false,
+      weak_warned: false,
     }
   }
 }
@@ -43,7 +46,21 @@ impl ConfirmPassword {
     self
   }
 
+  pub fn with_limits(mut self, limits: LengthLimits) -> Self {
+    self.password.set_limits(limits);
+    self.confirm.set_limits(limits);
+    self
+  }
+
+  pub fn with_strength_check(mut self) -> Self {
+    self.check_strength = true;
+    self
+  }
+
   pub fn on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    if key_event.kind == KeyEventKind::Press && key_event.code != KeyCode::Enter {
+      self.weak_warned = false;
+    }
     // press `Tab` to switch input
     if key_event.kind == KeyEventKind::Press && key_event.code == KeyCode::Tab {
       if self.password.is_active() {
@@ -76,6 +93,18 @@ impl ConfirmPassword {
       // if !self.msg.is_empty() {
       //   self.msg = "".to_owned();
       // }
+      if self.check_strength && !self.weak_warned {
+        let strength = estimate(self.password.content());
+        if strength.is_weak() {
+          let reason = strength.pattern.map_or("too short", |p| p.warning());
+          self.password.set_msg(format!(
+            "{} password ({reason}), enter again to keep it",
+            strength.label()
+          ));
+          self.weak_warned = true;
+          return false;
+        }
+      }
       return true;
     } else {
       self.confirm.set_msg("Not match");
@@ -89,6 +118,12 @@ impl ConfirmPassword {
   pub fn content(&self) -> &str {
     self.password.content()
   }
+
+  pub fn reset(&mut self) {
+    self.password.reset();
+    self.confirm.reset();
+    self.weak_warned = false;
+  }
 }
 
 pub fn draw_confirm_password(f: &mut Frame, state: &ConfirmPassword, area: Rect) {
@@ -1,17 +1,20 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
   layout::{Constraint, Layout, Rect},
-  style::{Color, Style},
+  style::{Color, Modifier, Style},
+  text::{Line, Span},
   widgets::{Block, Borders, Clear},
   Frame,
 };
 
 use crate::{
   common::TecResult,
+  generator::{generate, Policy},
   model::{Account, Pwd},
+  strength::{estimate, Estimate},
 };
 
-use super::{draw_input, Input};
+use super::{draw_input, Input, LengthLimits};
 
 #[derive(Clone, Copy)]
 enum FormMode {
@@ -40,6 +43,10 @@ pub struct Form {
   username: Input,
   password: Input,
   confirm: Input,
+  // of the password, a generated one is kept within them too
+  password_limits: LengthLimits,
+  // of the password, kept up to date as it is typed
+  strength: Estimate,
   // is_editing: bool,
 }
 
@@ -55,19 +62,26 @@ impl Default for Form {
       password: Input::default()
         .with_mask()
         .with_label("password: ")
-        .with_min(8)
-        .with_max(32),
+        .with_limits(LengthLimits::ACCOUNT_PASSWORD),
       confirm: Input::default()
         .with_mask()
         .with_label("confirm: ")
-        .with_min(8)
-        .with_max(32),
+        .with_limits(LengthLimits::ACCOUNT_PASSWORD),
+      password_limits: LengthLimits::ACCOUNT_PASSWORD,
+      strength: estimate(""),
       // is_editing: false,
     }
   }
 }
 
 impl Form {
+  pub fn with_password_limits(mut self, limits: LengthLimits) -> Self {
+    self.password.set_limits(limits);
+    self.confirm.set_limits(limits);
+    self.password_limits = limits;
+    self
+  }
+
   pub fn on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
     match key_event {
       KeyEvent {
@@ -79,6 +93,24 @@ impl Form {
         self.password.toggle_mask();
         self.confirm.toggle_mask();
       }
+      KeyEvent {
+        kind: KeyEventKind::Press,
+        modifiers: KeyModifiers::CONTROL,
+        code: KeyCode::Char('g'),
+        ..
+      } => {
+        let length = Policy::default()
+          .length
+          .clamp(self.password_limits.min, self.password_limits.max);
+        let pwd = generate(&Policy {
+          length,
+          // too short to hold every class
+          require_each: length >= 4,
+          ..Policy::default()
+        })?;
+        self.password.set_content(pwd.as_str());
+        self.confirm.set_content(pwd.as_str());
+      }
       KeyEvent {
         kind: KeyEventKind::Press,
         modifiers: KeyModifiers::CONTROL,
@@ -117,6 +149,7 @@ impl Form {
         FormMode::Confirm => self.confirm.on_key_event(key_event)?,
       },
     }
+    self.strength = estimate(self.password.content());
 
     Ok(())
   }
@@ -186,6 +219,7 @@ impl Form {
     self.username.reset();
     self.password.reset();
     self.confirm.reset();
+    self.strength = estimate("");
   }
 
   pub(crate) fn load_account(&mut self, a: &Account, pwd: Option<&Pwd>) -> TecResult<()> {
@@ -195,8 +229,9 @@ impl Form {
     self.confirm.reset();
 
     if let Some(p) = pwd {
-      self.password.set_content(&p.password);
+      self.password.set_content(p.password.as_str());
     }
+    self.strength = estimate(self.password.content());
     Ok(())
   }
 
@@ -224,10 +259,11 @@ pub fn draw_form(f: &mut Frame, form: &Form, area: Rect) {
   f.render_widget(block, area);
 
   let line_width = inner_area.width;
-  let [url_area, username_area, password_area, confirm_area] = Layout::vertical([
+  let [url_area, username_area, password_area, strength_area, confirm_area] = Layout::vertical([
     Constraint::Length(form.url.width().div_ceil(line_width as usize) as u16),
     Constraint::Length(form.username.width().div_ceil(line_width as usize) as u16),
     Constraint::Length(form.password.width().div_ceil(line_width as usize) as u16),
+    Constraint::Length(1),
     Constraint::Length(form.confirm.width().div_ceil(line_width as usize) as u16),
   ])
   .areas(inner_area);
@@ -235,5 +271,29 @@ pub fn draw_form(f: &mut Frame, form: &Form, area: Rect) {
   draw_input(f, &form.url, url_area);
   draw_input(f, &form.username, username_area);
   draw_input(f, &form.password, password_area);
+  if !form.password().is_empty() {
+    draw_strength(f, &form.strength, strength_area);
+  }
   draw_input(f, &form.confirm, confirm_area);
 }
+
+// e.g. `strength: ■■□□□ weak, a keyboard pattern`
+fn draw_strength(f: &mut Frame, strength: &Estimate, area: Rect) {
+  let color = match strength.score {
+    0 | 1 => Color::Red,
+    2 => Color::Yellow,
+    _ => Color::Green,
+  };
+  let filled = strength.score as usize + 1;
+  let mut text = format!(" {}", strength.label());
+  if let (true, Some(pattern)) = (strength.is_weak(), strength.pattern) {
+    text = format!("{text}, {}", pattern.warning());
+  }
+  let line = Line::from(vec![
+    Span::styled("strength: ", Style::default().add_modifier(Modifier::BOLD)),
+    Span::styled("■".repeat(filled), Style::default().fg(color)),
+    Span::raw("□".repeat(5 - filled)),
+    Span::styled(text, Style::default().fg(color)),
+  ]);
+  f.render_widget(line, area);
+}
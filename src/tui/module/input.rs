@@ -1,4 +1,4 @@
-use std::{cmp, usize};
+use std::{borrow::Cow, cmp, fmt, str::FromStr, usize};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
@@ -9,9 +9,61 @@ use ratatui::{
   Frame,
 };
 use unicode_width::UnicodeWidthStr;
+use zeroize::Zeroize;
 
 use crate::{common::TecResult, tui::util::get_pasted_content};
 
+// bounds on the length of an input, in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthLimits {
+  pub min: usize,
+  pub max: usize,
+}
+
+impl LengthLimits {
+  pub const MASTER_PASSWORD: Self = Self {
+    min: 8,
+    max: usize::MAX,
+  };
+  pub const ACCOUNT_PASSWORD: Self = Self {
+    min: 1,
+    max: usize::MAX,
+  };
+
+  pub fn contains(&self, len: usize) -> bool {
+    len >= self.min && len <= self.max
+  }
+}
+
+// `8-64`, or `8-` without a maximum
+impl FromStr for LengthLimits {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("invalid length {s}, expected min-max or min-");
+    let (min, max) = s.split_once('-').ok_or_else(invalid)?;
+    let min = min.parse().map_err(|_| invalid())?;
+    let max = match max {
+      "" => usize::MAX,
+      max => max.parse().map_err(|_| invalid())?,
+    };
+    if min > max {
+      return Err(invalid());
+    }
+    Ok(Self { min, max })
+  }
+}
+
+impl fmt::Display for LengthLimits {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.max == usize::MAX {
+      write!(f, "at least {}", self.min)
+    } else {
+      write!(f, "{}~{}", self.min, self.max)
+    }
+  }
+}
+
 pub struct Input {
   label: String,
   content: String,
@@ -65,6 +117,16 @@ impl Input {
     self
   }
 
+  pub fn with_limits(mut self, limits: LengthLimits) -> Self {
+    self.set_limits(limits);
+    self
+  }
+
+  pub fn set_limits(&mut self, limits: LengthLimits) {
+    self.min = limits.min;
+    self.max = limits.max;
+  }
+
   pub fn activate(&mut self) {
     self.is_active = true;
   }
@@ -86,7 +148,7 @@ impl Input {
   // }
 
   pub fn reset(&mut self) {
-    self.content = "".into();
+    self.content.zeroize();
     self.pos = 0;
   }
 
@@ -95,7 +157,8 @@ impl Input {
       return Ok(());
     }
 
-    let len = self.content.len();
+    let len = self.content.chars().count();
+    let mut truncated = false;
     match key_event {
       KeyEvent {
         code: KeyCode::Char('v'),
@@ -103,10 +166,14 @@ impl Input {
         kind: KeyEventKind::Press,
         ..
       } => {
-        if len < self.max {
-          if let Some(pasted) = get_pasted_content()? {
-            self.insert_str(&pasted);
-          }
+        if let Some(pasted) = get_pasted_content()? {
+          let room = self.max.saturating_sub(len);
+          let end = pasted
+            .char_indices()
+            .nth(room)
+            .map_or(pasted.len(), |(i, _)| i);
+          truncated = end < pasted.len();
+          self.insert_str(&pasted[..end]);
         }
       }
       KeyEvent {
@@ -129,19 +196,26 @@ impl Input {
     if !self.msg.is_empty() {
       self.msg = "".to_string();
     }
+    // don't let a paste lose its end unnoticed
+    if truncated {
+      self.msg = format!("The paste is cut to {} characters", self.max);
+    }
 
     Ok(())
   }
 
   pub fn validate(&mut self) -> bool {
-    let len = self.content.len();
-    if len >= self.min && len <= self.max {
+    let limits = LengthLimits {
+      min: self.min,
+      max: self.max,
+    };
+    if limits.contains(self.content.chars().count()) {
       if !self.msg.is_empty() {
         self.msg = "".to_string();
       }
       true
     } else {
-      self.msg = format!("The length is required be {}~{}", self.min, self.max,);
+      self.msg = format!("The length is required be {limits}");
       false
     }
   }
@@ -162,6 +236,7 @@ impl Input {
 
   fn enter_char(&mut self, ch: char) {
     let index = self.byte_idnex();
+    self.reserve(ch.len_utf8());
     self.content.insert(index, ch);
     self.move_cursor_right();
   }
@@ -169,24 +244,32 @@ impl Input {
   fn delete_char(&mut self) {
     let leftmost = self.pos == 0;
     if !leftmost {
-      let curr = self.pos;
-      let from = curr - 1;
-      let before = self.content.chars().take(from);
-      let after = self.content.chars().skip(curr);
-      self.content = before.chain(after).collect();
       self.move_cursor_left();
+      let index = self.byte_idnex();
+      self.content.remove(index);
     }
   }
 
   fn insert_str(&mut self, s: &str) {
-    let curr = self.pos;
-    let before = self.content.chars().take(curr);
-    let after = self.content.chars().skip(curr);
-    self.content = before.chain(s.chars()).chain(after).collect();
-    let right = self.pos.saturating_add(s.len());
+    let index = self.byte_idnex();
+    self.reserve(s.len());
+    self.content.insert_str(index, s);
+    let right = self.pos.saturating_add(s.chars().count());
     self.pos = self.clamp_cursor(right);
   }
 
+  // the content may be a password, so grow the buffer by hand and wipe the old one
+  // instead of leaving it behind in freed memory
+  fn reserve(&mut self, additional: usize) {
+    let required = self.content.len() + additional;
+    if required > self.content.capacity() {
+      let mut content = String::with_capacity(cmp::max(required, self.content.capacity() * 2));
+      content.push_str(&self.content);
+      self.content.zeroize();
+      self.content = content;
+    }
+  }
+
   fn byte_idnex(&self) -> usize {
     self
       .content
@@ -209,7 +292,9 @@ impl Input {
   }
 
   pub(crate) fn set_content(&mut self, content: impl Into<String>) {
+    self.content.zeroize();
     self.content = content.into();
+    self.pos = self.content.chars().count();
   }
 
   pub fn width(&self) -> usize {
@@ -217,14 +302,20 @@ impl Input {
   }
 }
 
+impl Drop for Input {
+  fn drop(&mut self) {
+    self.content.zeroize();
+  }
+}
+
 pub fn draw_input(f: &mut Frame, state: &Input, area: Rect) {
   let label = Span::styled(&state.label, Style::default().add_modifier(Modifier::BOLD));
 
-  let content = {
+  let content: Cow<str> = {
     if state.is_masked {
-      String::from_iter(state.content.chars().map(|_| '*'))
+      Cow::Owned(String::from_iter(state.content.chars().map(|_| '*')))
     } else {
-      state.content.clone()
+      Cow::Borrowed(&state.content)
     }
   };
 
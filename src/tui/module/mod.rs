@@ -3,11 +3,13 @@ mod confirm;
 mod confirm_password;
 mod form;
 mod input;
+mod report;
 mod view;
 
 pub use self::account_table::{draw_account_table, AccountTable};
 pub use self::confirm::{draw_confirm, Confirm};
 pub use self::confirm_password::{draw_confirm_password, ConfirmPassword};
 pub use self::form::{draw_form, Form};
-pub use self::input::{draw_input, Input};
+pub use self::input::{draw_input, Input, LengthLimits};
+pub use self::report::{draw_report, Report};
 pub use self::view::{draw_view, View};
@@ -0,0 +1,94 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+  layout::Rect,
+  style::{Color, Modifier, Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Borders, Clear, List, ListItem, ListState},
+  Frame,
+};
+
+use crate::{audit::Finding, common::TecResult};
+
+// the accounts whose password is weak, reused or old
+pub struct Report {
+  findings: Vec<Finding>,
+  state: ListState,
+}
+
+impl Default for Report {
+  fn default() -> Self {
+    Self {
+      findings: vec![],
+      state: ListState::default().with_selected(Some(0)),
+    }
+  }
+}
+
+impl Report {
+  pub fn load(&mut self, findings: Vec<Finding>) {
+    self.findings = findings;
+    self.state.select(Some(0));
+  }
+
+  pub fn reset(&mut self) {
+    self.findings = vec![];
+  }
+
+  pub(crate) fn selected(&self) -> Option<u32> {
+    let index = self.state.selected()?;
+    self.findings.get(index).map(|f| f.account.id)
+  }
+
+  pub(crate) fn on_key_event(&mut self, key_event: KeyEvent) -> TecResult<()> {
+    if key_event.kind != KeyEventKind::Press {
+      return Ok(());
+    }
+    let len = self.findings.len();
+    if len == 0 {
+      return Ok(());
+    }
+    let selected = self.state.selected().unwrap_or(0);
+    match key_event.code {
+      KeyCode::Char('j') | KeyCode::Down => self.state.select(Some((selected + 1) % len)),
+      KeyCode::Char('k') | KeyCode::Up => self.state.select(Some((selected + len - 1) % len)),
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
+pub fn draw_report(f: &mut Frame, report: &mut Report, area: Rect) {
+  let block = Block::default()
+    .title(format!(
+      "Health Report ({} accounts)",
+      report.findings.len()
+    ))
+    .borders(Borders::ALL)
+    .style(Style::default().bg(Color::Black).fg(Color::White));
+
+  let items: Vec<ListItem> = if report.findings.is_empty() {
+    vec![Line::raw("no weak, reused or old passwords").green().into()]
+  } else {
+    report
+      .findings
+      .iter()
+      .map(|finding| {
+        let issues: Vec<String> = finding.issues.iter().map(|i| i.to_string()).collect();
+        Line::from(vec![
+          Span::styled(&finding.account.url, Style::default().bold()),
+          Span::raw(format!(" {} ", finding.account.username)),
+          Span::styled(issues.join("; "), Style::default().fg(Color::Red)),
+        ])
+        .into()
+      })
+      .collect()
+  };
+
+  let list = List::new(items)
+    .block(block)
+    .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    .highlight_symbol("❯");
+
+  f.render_widget(Clear, area);
+  f.render_stateful_widget(list, area, &mut report.state);
+}
@@ -9,13 +9,15 @@ use ratatui::{
 
 use crate::{
   common::TecResult,
+  memory,
   model::{Account, Pwd},
-  tui::util::{copy_content, millis2string},
+  tui::util::{copy_content, copy_secret, millis2string, CopiedSecret},
 };
 
 pub struct View {
   account: Option<Account>,
   pwds: Option<Vec<Pwd>>,
+  copied: Option<CopiedSecret>,
   is_masked: bool,
   state: ListState,
   symbol: String,
@@ -26,6 +28,7 @@ impl Default for View {
     Self {
       account: None,
       pwds: None,
+      copied: None,
       is_masked: true,
       state: ListState::default().with_selected(Some(0)),
       symbol: "❯".into(),
@@ -40,9 +43,25 @@ impl View {
   }
 
   pub fn load_pwds(&mut self, pwds: Vec<Pwd>) {
+    // shown for as long as the view is open
+    for pwd in pwds.iter() {
+      let _ = memory::lock(pwd.password.as_bytes());
+    }
     self.pwds = Some(pwds);
   }
 
+  // drop the decrypted passwords once the view is closed
+  pub fn reset(&mut self) {
+    self.account = None;
+    self.pwds = None;
+    self.is_masked = true;
+  }
+
+  // the password copied since the last call, for the app to clear it in time
+  pub(crate) fn take_copied(&mut self) -> Option<CopiedSecret> {
+    self.copied.take()
+  }
+
   // pub fn show_pwds(&mut self) {
   //   self.is_masked = false;
   // }
@@ -93,7 +112,7 @@ impl View {
       Some(2) => {
         if let Some(pwds) = self.pwds.as_ref() {
           if let Some(pwd) = pwds.get(0) {
-            copy_content(pwd.password.as_bytes())?;
+            self.copied = Some(copy_secret(pwd.password.as_bytes())?);
           }
         }
       }
@@ -147,14 +166,13 @@ pub fn draw_view(f: &mut Frame, view: &mut View, area: Rect) {
   let mut pwds_line = Line::from(vec![Span::styled("passwords: ", Style::default().bold())]);
   if view.is_masked {
     pwds_line.push_span(Span::raw("*******"))
-  } else if let Some(pwds) = view.pwds.as_mut() {
-    let pwds_str = pwds
-      .iter()
-      .map(|p| p.password.clone())
-      .collect::<Vec<String>>()
-      .join("; ");
-    pwds_line.push_span(Span::raw(pwds_str));
-    // pwds.iter().map(|p| p.password)
+  } else if let Some(pwds) = view.pwds.as_ref() {
+    for (i, p) in pwds.iter().enumerate() {
+      if i > 0 {
+        pwds_line.push_span(Span::raw("; "));
+      }
+      pwds_line.push_span(Span::raw(p.password.as_str()));
+    }
   }
   let pwds_item: ListItem = pwds_line.into();
 
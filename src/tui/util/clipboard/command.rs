@@ -0,0 +1,126 @@
+use std::{
+  io::{Read, Write},
+  os::unix::process::CommandExt,
+  process::{Child, Command as Process, ExitStatus, Stdio},
+  sync::mpsc,
+  thread,
+  time::{Duration, Instant},
+};
+
+use zeroize::Zeroizing;
+
+use crate::common::{secret_string, SecretBytes, SecretString, TecError, TecResult};
+
+use super::ClipboardBackend;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// pipes the content into and out of user supplied commands, e.g. `xclip -selection
+// clipboard` and `xclip -o -selection clipboard`, run with `sh -c`
+pub struct Command {
+  copy: String,
+  paste: Option<String>,
+}
+
+impl Command {
+  pub fn new(copy: String, paste: Option<String>) -> Self {
+    Self { copy, paste }
+  }
+}
+
+impl ClipboardBackend for Command {
+  fn copy(&self, bytes: &[u8], _secret: bool) -> TecResult<()> {
+    // copy commands often fork to serve the clipboard, which would keep a pipe open
+    let mut child = Process::new("sh")
+      .arg("-c")
+      .arg(&self.copy)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+      stdin.write_all(bytes)?;
+    }
+    let status = wait(&mut child, &self.copy)?;
+    check(status, &self.copy)
+  }
+
+  fn paste(&self) -> TecResult<Option<SecretString>> {
+    let Some(paste) = &self.paste else {
+      return Ok(None);
+    };
+    let start = Instant::now();
+    // in a group of its own, so whatever it leaves running on the pipe can be killed
+    let mut child = Process::new("sh")
+      .arg("-c")
+      .arg(paste)
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .process_group(0)
+      .spawn()?;
+    let mut stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+      let mut contents: SecretBytes = Zeroizing::new(vec![]);
+      let res = stdout.read_to_end(&mut contents).map(|_| contents);
+      let _ = tx.send(res);
+    });
+
+    let status = wait(&mut child, paste).inspect_err(|_| kill_group(&child))?;
+    // a process forked into the background may keep the pipe open after the command exits
+    let contents = match rx.recv_timeout(TIMEOUT.saturating_sub(start.elapsed())) {
+      Ok(res) => res?,
+      Err(_) => {
+        kill_group(&child);
+        return Err(TecError::Clipboard(format!(
+          "`{paste}` kept its output open after {}s",
+          TIMEOUT.as_secs()
+        )));
+      }
+    };
+    check(status, paste)?;
+    if contents.is_empty() {
+      return Ok(None);
+    }
+    Ok(Some(secret_string(contents)?))
+  }
+
+  fn clear(&self) -> TecResult<()> {
+    self.copy(&[], false)
+  }
+
+  fn is_readable(&self) -> bool {
+    self.paste.is_some()
+  }
+}
+
+fn wait(child: &mut Child, cmd: &str) -> TecResult<ExitStatus> {
+  let start = Instant::now();
+  loop {
+    if let Some(status) = child.try_wait()? {
+      return Ok(status);
+    }
+    if start.elapsed() >= TIMEOUT {
+      child.kill()?;
+      child.wait()?;
+      return Err(TecError::Clipboard(format!(
+        "`{cmd}` timed out after {}s",
+        TIMEOUT.as_secs()
+      )));
+    }
+    thread::sleep(Duration::from_millis(10));
+  }
+}
+
+fn kill_group(child: &Child) {
+  unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+}
+
+fn check(status: ExitStatus, cmd: &str) -> TecResult<()> {
+  if status.success() {
+    Ok(())
+  } else {
+    Err(TecError::Clipboard(format!("`{cmd}` failed - {status}")))
+  }
+}
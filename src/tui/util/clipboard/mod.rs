@@ -0,0 +1,179 @@
+use std::{
+  env,
+  path::PathBuf,
+  str::FromStr,
+  sync::OnceLock,
+  time::{Duration, Instant},
+};
+
+use sha3::{Digest, Sha3_256};
+
+use crate::common::{SecretString, TecError, TecResult};
+
+mod command;
+mod osc52;
+mod wayland;
+mod x11;
+
+use self::{command::Command, osc52::Osc52, wayland::Wayland, x11::X11};
+
+// asks clipboard managers, e.g. klipper, not to keep the copied content in their history
+const PASSWORD_HINT_MIME: &str = "x-kde-passwordManagerHint";
+
+// picked on first use, and kept for the lifetime of the process as some backends have to
+// serve the copied content themselves
+static BACKEND: OnceLock<Box<dyn ClipboardBackend + Send + Sync>> = OnceLock::new();
+
+pub trait ClipboardBackend {
+  // `secret` asks the backend to hide the content from clipboard managers, if it can
+  fn copy(&self, bytes: &[u8], secret: bool) -> TecResult<()>;
+  fn paste(&self) -> TecResult<Option<SecretString>>;
+  fn clear(&self) -> TecResult<()>;
+
+  // whether `paste` can tell what is on the clipboard
+  fn is_readable(&self) -> bool {
+    true
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ClipboardKind {
+  Auto,
+  Wayland,
+  X11,
+  Osc52,
+}
+
+impl FromStr for ClipboardKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(Self::Auto),
+      "wayland" => Ok(Self::Wayland),
+      "x11" => Ok(Self::X11),
+      "osc52" => Ok(Self::Osc52),
+      _ => Err(format!(
+        "unknown clipboard {s}, expected auto, wayland, x11 or osc52"
+      )),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct ClipboardOptions {
+  pub kind: ClipboardKind,
+  // how long a copied secret stays on the clipboard
+  pub clear_after: Option<Duration>,
+  // shell commands to copy from stdin and paste to stdout, used instead of `kind`
+  pub copy_command: Option<String>,
+  pub paste_command: Option<String>,
+}
+
+// a secret that was put on the clipboard, only its digest is kept to tell whether the
+// clipboard still holds it
+pub struct CopiedSecret {
+  digest: Vec<u8>,
+  copied_at: Instant,
+}
+
+impl CopiedSecret {
+  pub fn elapsed(&self) -> Duration {
+    self.copied_at.elapsed()
+  }
+
+  // clear the clipboard, unless something else has been copied since. a clipboard that
+  // can't be read is cleared anyway, rather than leaving the secret behind.
+  pub fn clear(&self) -> TecResult<()> {
+    let backend = backend()?;
+    if !backend.is_readable() {
+      return backend.clear().map_err(clipboard_error);
+    }
+    if let Some(content) = backend.paste().map_err(clipboard_error)? {
+      if digest(content.as_bytes()) == self.digest {
+        backend.clear().map_err(clipboard_error)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+// pick the clipboard before the first copy, so that a forced one that isn't available
+// fails right away
+pub fn init_clipboard(opts: &ClipboardOptions) -> TecResult<()> {
+  if BACKEND.get().is_some() {
+    return Ok(());
+  }
+  let backend: Box<dyn ClipboardBackend + Send + Sync> = match &opts.copy_command {
+    Some(copy) => Box::new(Command::new(copy.clone(), opts.paste_command.clone())),
+    None => select_backend(opts.kind)?,
+  };
+  let _ = BACKEND.set(backend);
+  Ok(())
+}
+
+fn backend() -> TecResult<&'static dyn ClipboardBackend> {
+  if BACKEND.get().is_none() {
+    let _ = BACKEND.set(select_backend(ClipboardKind::Auto).map_err(clipboard_error)?);
+  }
+  Ok(BACKEND.get().unwrap().as_ref())
+}
+
+// every failure of the clipboard is reported the same way, so that the app can show it
+// instead of quitting
+fn clipboard_error(err: TecError) -> TecError {
+  match err {
+    TecError::Clipboard(_) => err,
+    _ => TecError::Clipboard(err.to_string()),
+  }
+}
+
+// wayland is preferred, as X11 is usually also reachable through xwayland. without a
+// display server, e.g. over ssh, the terminal is asked to set its clipboard.
+fn select_backend(kind: ClipboardKind) -> TecResult<Box<dyn ClipboardBackend + Send + Sync>> {
+  match kind {
+    ClipboardKind::Wayland => Ok(Box::new(Wayland)),
+    ClipboardKind::X11 => Ok(Box::new(X11::new()?)),
+    ClipboardKind::Osc52 => Ok(Box::new(Osc52)),
+    ClipboardKind::Auto => {
+      if wayland_socket().is_some_and(|socket| socket.exists()) {
+        return Ok(Box::new(Wayland));
+      }
+      if env::var_os("DISPLAY").is_some() {
+        if let Ok(x11) = X11::new() {
+          return Ok(Box::new(x11));
+        }
+      }
+      Ok(Box::new(Osc52))
+    }
+  }
+}
+
+fn wayland_socket() -> Option<PathBuf> {
+  let display = PathBuf::from(env::var_os("WAYLAND_DISPLAY")?);
+  if display.is_absolute() {
+    return Some(display);
+  }
+  Some(PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?).join(display))
+}
+
+pub fn get_pasted_content() -> TecResult<Option<SecretString>> {
+  backend()?.paste().map_err(clipboard_error)
+}
+
+pub fn copy_content(bytes: &[u8]) -> TecResult<()> {
+  backend()?.copy(bytes, false).map_err(clipboard_error)
+}
+
+// copy a password, marked as secret for clipboard managers
+pub fn copy_secret(bytes: &[u8]) -> TecResult<CopiedSecret> {
+  backend()?.copy(bytes, true).map_err(clipboard_error)?;
+  Ok(CopiedSecret {
+    digest: digest(bytes),
+    copied_at: Instant::now(),
+  })
+}
+
+fn digest(bytes: &[u8]) -> Vec<u8> {
+  Sha3_256::digest(bytes).to_vec()
+}
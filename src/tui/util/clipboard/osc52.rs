@@ -0,0 +1,59 @@
+use std::{env, io};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{execute, style::Print};
+use zeroize::Zeroizing;
+
+use crate::common::{SecretString, TecResult};
+
+use super::ClipboardBackend;
+
+// screen cuts DCS strings longer than this
+const SCREEN_CHUNK_LEN: usize = 768;
+
+// asks the terminal to set its clipboard with an OSC 52 escape sequence, which also works
+// over ssh. the terminal's clipboard can't be read back, the terminal's own paste has to
+// be used instead.
+pub struct Osc52;
+
+impl ClipboardBackend for Osc52 {
+  fn copy(&self, bytes: &[u8], _secret: bool) -> TecResult<()> {
+    let encoded = Zeroizing::new(STANDARD.encode(bytes));
+    write(&encoded)
+  }
+
+  fn paste(&self) -> TecResult<Option<SecretString>> {
+    Ok(None)
+  }
+
+  // anything but base64 clears the clipboard
+  fn clear(&self) -> TecResult<()> {
+    write("!")
+  }
+
+  fn is_readable(&self) -> bool {
+    false
+  }
+}
+
+fn write(payload: &str) -> TecResult<()> {
+  let seq = Zeroizing::new(format!("\x1b]52;c;{payload}\x07"));
+  let seq = if env::var_os("TMUX").is_some() {
+    // needs `set -g allow-passthrough on` in tmux 3.3 and later
+    Zeroizing::new(format!(
+      "\x1bPtmux;{}\x1b\\",
+      seq.replace('\x1b', "\x1b\x1b")
+    ))
+  } else if env::var("TERM").is_ok_and(|term| term.starts_with("screen")) {
+    let chunks: Vec<String> = seq
+      .as_bytes()
+      .chunks(SCREEN_CHUNK_LEN)
+      .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+      .collect();
+    Zeroizing::new(chunks.concat())
+  } else {
+    seq
+  };
+  execute!(io::stdout(), Print(seq.as_str()))?;
+  Ok(())
+}
@@ -0,0 +1,57 @@
+use std::io::Read;
+
+use wl_clipboard_rs::{
+  copy::{self, MimeSource, MimeType as CopyMimeType, Options, Source},
+  paste::{get_contents, ClipboardType, Error, MimeType, Seat},
+};
+use zeroize::Zeroizing;
+
+use crate::common::{secret_string, SecretString, TecResult};
+
+use super::{ClipboardBackend, PASSWORD_HINT_MIME};
+
+pub struct Wayland;
+
+impl ClipboardBackend for Wayland {
+  fn copy(&self, bytes: &[u8], secret: bool) -> TecResult<()> {
+    let opts = Options::new();
+    if !secret {
+      opts.copy(Source::Bytes(bytes.into()), CopyMimeType::Autodetect)?;
+      return Ok(());
+    }
+    opts.copy_multi(vec![
+      MimeSource {
+        source: Source::Bytes(bytes.into()),
+        mime_type: CopyMimeType::Text,
+      },
+      MimeSource {
+        source: Source::Bytes(b"secret"[..].into()),
+        mime_type: CopyMimeType::Specific(PASSWORD_HINT_MIME.to_owned()),
+      },
+    ])?;
+    Ok(())
+  }
+
+  fn paste(&self) -> TecResult<Option<SecretString>> {
+    let result = get_contents(ClipboardType::Regular, Seat::Unspecified, MimeType::Text);
+    match result {
+      Ok((mut pipe, _)) => {
+        let mut contents = Zeroizing::new(vec![]);
+        pipe.read_to_end(&mut contents)?;
+        Ok(Some(secret_string(contents)?))
+      }
+
+      Err(Error::NoSeats) | Err(Error::ClipboardEmpty) | Err(Error::NoMimeType) => {
+        Ok(None)
+        // The clipboard is empty or doesn't contain text, nothing to worry about.
+      }
+
+      Err(err) => Err(err)?,
+    }
+  }
+
+  fn clear(&self) -> TecResult<()> {
+    copy::clear(copy::ClipboardType::Regular, copy::Seat::All)?;
+    Ok(())
+  }
+}
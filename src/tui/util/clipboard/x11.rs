@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use x11_clipboard::{error::Error, Clipboard};
+use zeroize::Zeroizing;
+
+use crate::common::{secret_string, SecretString, TecResult};
+
+use super::ClipboardBackend;
+
+const PASTE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// the selection is served by a thread of `Clipboard`, so a copy only lasts as long as the
+// process, unless a clipboard manager takes it over. only one target can be offered,
+// hence no password hint.
+pub struct X11 {
+  clipboard: Clipboard,
+}
+
+impl X11 {
+  pub fn new() -> TecResult<Self> {
+    Ok(Self {
+      clipboard: Clipboard::new()?,
+    })
+  }
+}
+
+impl ClipboardBackend for X11 {
+  fn copy(&self, bytes: &[u8], _secret: bool) -> TecResult<()> {
+    let atoms = &self.clipboard.setter.atoms;
+    self
+      .clipboard
+      .store(atoms.clipboard, atoms.utf8_string, bytes)?;
+    Ok(())
+  }
+
+  fn paste(&self) -> TecResult<Option<SecretString>> {
+    let atoms = &self.clipboard.getter.atoms;
+    let result = self.clipboard.load(
+      atoms.clipboard,
+      atoms.utf8_string,
+      atoms.property,
+      PASTE_TIMEOUT,
+    );
+    match result {
+      Ok(contents) if contents.is_empty() => Ok(None),
+      Ok(contents) => Ok(Some(secret_string(Zeroizing::new(contents))?)),
+      // nobody owns the clipboard, or it doesn't hold text
+      Err(Error::Timeout) | Err(Error::UnexpectedType(_)) => Ok(None),
+      Err(err) => Err(err)?,
+    }
+  }
+
+  fn clear(&self) -> TecResult<()> {
+    self.copy(&[], false)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // needs an X server, e.g. `xvfb-run cargo test -- --ignored`
+  #[test]
+  #[ignore]
+  fn copy_paste_clear() {
+    let x11 = X11::new().unwrap();
+    x11.copy(b"hunter2", true).unwrap();
+    assert_eq!(
+      x11.paste().unwrap().as_deref().map(|s| s.as_str()),
+      Some("hunter2")
+    );
+    x11.clear().unwrap();
+    assert_eq!(x11.paste().unwrap(), None);
+  }
+}
@@ -1,5 +1,8 @@
 mod clipboard;
 mod time;
 
-pub use clipboard::{copy_content, get_pasted_content};
+pub use clipboard::{
+  copy_content, copy_secret, get_pasted_content, init_clipboard, ClipboardKind, ClipboardOptions,
+  CopiedSecret,
+};
 pub use time::{current_millis, millis2string};